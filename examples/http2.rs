@@ -2,10 +2,13 @@ extern crate chttp;
 
 
 fn main() {
-    let mut options = chttp::Options::default();
-    options.preferred_http_version = Some(chttp::http::Version::HTTP_2);
+    let options = chttp::Options::default()
+        .with_preferred_http_version(Some(chttp::http::Version::HTTP_2));
 
-    let client = chttp::Client::with_options(options);
+    let client = chttp::Client::builder()
+        .options(options)
+        .build()
+        .unwrap();
 
     let mut response = client.get("https://nghttp2.org").unwrap();
     let body = response.body_mut().text().unwrap();
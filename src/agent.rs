@@ -0,0 +1,362 @@
+use curl;
+use curl::multi::MultiWaker;
+use curl_sys;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use share::Share;
+use super::*;
+use transport::{self, Collector};
+
+
+const WAIT_TIMEOUT_MS: u64 = 1000;
+
+
+/// A handle to a background agent thread that executes requests.
+///
+/// Each agent owns a single curl multi handle, and all transfers started through the agent are multiplexed on that
+/// handle by its thread. The agent thread keeps running until all handles to it have been dropped and all of its
+/// transfers are complete.
+#[derive(Clone)]
+pub struct Agent {
+    /// Channel for sending messages to the agent thread.
+    sender: Arc<Mutex<mpsc::Sender<Message>>>,
+    /// Used to interrupt the agent while it is waiting for activity.
+    waker: Arc<MultiWaker>,
+}
+
+/// A handle to an agent thread that does not keep the thread running.
+pub struct WeakAgent {
+    sender: Weak<Mutex<mpsc::Sender<Message>>>,
+    waker: Weak<MultiWaker>,
+}
+
+impl WeakAgent {
+    /// Get a handle to the agent thread, if it is still running.
+    pub fn upgrade(&self) -> Option<Agent> {
+        Some(Agent {
+            sender: self.sender.upgrade()?,
+            waker: self.waker.upgrade()?,
+        })
+    }
+}
+
+/// A message sent from a handle to the agent thread.
+enum Message {
+    /// Begin executing a new transfer.
+    Begin(curl::easy::Easy2<Collector>),
+    /// Abort a transfer that is no longer wanted, failing it with `ErrorKind::Cancelled`.
+    Cancel(usize),
+    /// Resume writing to a transfer that was paused because its response buffer was full.
+    Unpause(usize),
+}
+
+impl Agent {
+    /// Spawn a new agent thread and return a handle to it.
+    ///
+    /// The given connection limits are applied to the agent's connection pool, and all transfers executed by the agent
+    /// are attached to the given share handle.
+    pub fn new(max_connections: Option<usize>, max_connections_per_host: Option<usize>, share: Arc<Share>) -> Result<Agent, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let (ready_sender, ready_receiver) = mpsc::sync_channel(1);
+
+        thread::Builder::new()
+            .name(String::from("chttp-agent"))
+            .spawn(move || {
+                // Multi handles cannot be moved between threads, so the handle must be created and configured on the
+                // agent thread itself.
+                let mut multi = curl::multi::Multi::new();
+
+                let result = configure_pool(&mut multi, max_connections, max_connections_per_host);
+                let failed = result.is_err();
+                ready_sender.send(result.map(|()| multi.waker())).ok();
+                if failed {
+                    return;
+                }
+
+                AgentThread {
+                    multi: multi,
+                    handles: HashMap::new(),
+                    retries: Vec::new(),
+                    receiver: receiver,
+                    share: share,
+                }.run()
+            })
+            .map_err(|e| ErrorKind::AgentFailed(format!("failed to start agent thread: {}", e)))?;
+
+        let waker = ready_receiver.recv().map_err(|_| terminated())??;
+
+        Ok(Agent {
+            sender: Arc::new(Mutex::new(sender)),
+            waker: Arc::new(waker),
+        })
+    }
+
+    /// Begin executing a transfer on the agent thread.
+    pub fn begin(&self, easy: curl::easy::Easy2<Collector>) -> Result<(), Error> {
+        self.send(Message::Begin(easy))
+    }
+
+    /// Get a handle to the agent that does not keep its thread running.
+    pub fn downgrade(&self) -> WeakAgent {
+        WeakAgent {
+            sender: Arc::downgrade(&self.sender),
+            waker: Arc::downgrade(&self.waker),
+        }
+    }
+
+    /// Abort the transfer with the given ID.
+    pub fn cancel(&self, id: usize) -> Result<(), Error> {
+        self.send(Message::Cancel(id))
+    }
+
+    /// Resume a paused transfer with the given ID.
+    pub fn unpause(&self, id: usize) -> Result<(), Error> {
+        self.send(Message::Unpause(id))
+    }
+
+    fn send(&self, message: Message) -> Result<(), Error> {
+        self.sender.lock().unwrap()
+            .send(message)
+            .map_err(|_| terminated())?;
+
+        // Wake the agent so that it handles the message right away. This only fails if the multi handle is gone.
+        self.waker.wakeup().map_err(|_| terminated())
+    }
+}
+
+
+/// Apply connection limits to the connection pool of a multi handle.
+fn configure_pool(multi: &mut curl::multi::Multi, max_connections: Option<usize>, max_connections_per_host: Option<usize>) -> Result<(), Error> {
+    // Transfers that would exceed these limits are queued by curl until a connection becomes available.
+    if let Some(max) = max_connections {
+        multi.set_max_total_connections(max)?;
+
+        // Never keep more idle connections around than we are allowed to have open.
+        multi.set_max_connects(max)?;
+    }
+
+    if let Some(max) = max_connections_per_host {
+        multi.set_max_host_connections(max)?;
+    }
+
+    Ok(())
+}
+
+
+/// State owned by the agent thread itself.
+struct AgentThread {
+    /// A curl multi handle used to execute all transfers.
+    multi: curl::multi::Multi,
+    /// Handles for all active transfers, keyed by transfer ID.
+    handles: HashMap<usize, curl::multi::Easy2Handle<Collector>>,
+    /// Transfers waiting to be sent again, along with the time to send them at.
+    retries: Vec<(Instant, curl::easy::Easy2<Collector>)>,
+    /// Incoming messages from agent handles.
+    receiver: mpsc::Receiver<Message>,
+    /// A share handle to attach new transfers to. Declared after the handles so that it outlives them.
+    share: Arc<Share>,
+}
+
+impl AgentThread {
+    /// Run the agent loop until there are no more handles to the agent and no active transfers.
+    fn run(mut self) {
+        debug!("agent started");
+
+        loop {
+            // Handle any pending messages.
+            loop {
+                match self.receiver.try_recv() {
+                    Ok(message) => self.handle_message(message),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        if self.handles.is_empty() && self.retries.is_empty() {
+                            debug!("agent shutting down");
+                            return;
+                        }
+                        break;
+                    }
+                }
+            }
+
+            if let Err(e) = self.dispatch() {
+                error!("agent failed: {}", e);
+                self.fail_all(e);
+                return;
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: Message) {
+        match message {
+            Message::Begin(easy) => {
+                // The share handle outlives all of our easy handles.
+                if let Err(e) = unsafe { self.share.attach(easy.raw()) } {
+                    easy.get_ref().transfer().complete(Err(e));
+                    return;
+                }
+
+                self.add(easy);
+            }
+            Message::Cancel(id) => {
+                if let Some(handle) = self.handles.remove(&id) {
+                    trace!("canceling transfer {}", id);
+                    if let Ok(easy) = self.multi.remove2(handle) {
+                        easy.get_ref().finish(Err(ErrorKind::Cancelled.into()));
+                    }
+                }
+
+                let (cancelled, retries) = self.retries.drain(..).partition::<Vec<_>, _>(|&(_, ref easy)| easy.get_ref().transfer().id() == id);
+                self.retries = retries;
+                for (_, easy) in cancelled {
+                    easy.get_ref().finish(Err(ErrorKind::Cancelled.into()));
+                }
+            }
+            Message::Unpause(id) => {
+                if let Some(handle) = self.handles.get(&id) {
+                    if let Err(e) = handle.unpause_write() {
+                        warn!("failed to unpause transfer {}: {}", id, e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Add an easy handle to the multi handle so that it starts executing.
+    fn add(&mut self, mut easy: curl::easy::Easy2<Collector>) {
+        easy.get_mut().begin_attempt();
+        let transfer = easy.get_ref().transfer().clone();
+        let id = transfer.id();
+
+        match self.multi.add2(easy) {
+            Ok(mut handle) => match handle.set_token(id) {
+                Ok(()) => {
+                    self.handles.insert(id, handle);
+                }
+                Err(e) => {
+                    // Detach the handle again so that it is not left behind in the multi handle.
+                    self.multi.remove2(handle).ok();
+                    transfer.complete(Err(e.into()));
+                }
+            },
+            Err(e) => transfer.complete(Err(e.into())),
+        }
+    }
+
+    /// Dispatch reads and writes for all active transfers, blocking the current thread if necessary.
+    fn dispatch(&mut self) -> Result<(), Error> {
+        // Start any transfers that are due to be sent again.
+        let now = Instant::now();
+        let (due, waiting) = self.retries.drain(..).partition::<Vec<_>, _>(|&(time, _)| time <= now);
+        self.retries = waiting;
+        for (_, easy) in due {
+            self.add(easy);
+        }
+
+        // Determine the blocking timeout value, waking up in time for the next transfer to send again, and for the
+        // next first byte timeout.
+        let mut max_timeout = Duration::from_millis(WAIT_TIMEOUT_MS);
+        if let Some(time) = self.retries.iter().map(|&(time, _)| time).min() {
+            max_timeout = max_timeout.min(time - now);
+        }
+        if let Some(time) = self.handles.values().filter_map(|handle| handle.get_ref().first_byte_deadline()).min() {
+            max_timeout = max_timeout.min(if time > now { time - now } else { Duration::from_secs(0) });
+        }
+        let timeout = match self.multi.get_timeout()? {
+            Some(timeout) if timeout < max_timeout => timeout,
+            _ => max_timeout,
+        };
+
+        // Block until activity is detected, a message arrives and wakes us, or the timeout passes.
+        self.multi.poll(&mut [], timeout)?;
+
+        // Perform any pending reads or writes.
+        self.multi.perform()?;
+
+        // Collect the results of any transfers that have stopped. Read messages before we detach the easy handles, or
+        // the messages will be discarded.
+        let mut finished = Vec::new();
+        self.multi.messages(|message| {
+            trace!("curl message: {:?}", message);
+            if let (Ok(id), Some(result)) = (message.token(), message.result()) {
+                finished.push((id, result));
+            }
+        });
+
+        // Curl has no option for a first byte timeout, so stop transfers whose server has not started responding in
+        // time ourselves. They are handled like any other transfer that timed out.
+        let now = Instant::now();
+        for (&id, handle) in self.handles.iter_mut() {
+            let expired = handle.get_ref().first_byte_deadline().map_or(false, |time| time <= now);
+
+            if expired && !finished.iter().any(|&(finished_id, _)| finished_id == id) {
+                handle.get_mut().abort(ErrorKind::FirstByteTimeout.into());
+                finished.push((id, Err(curl::Error::new(curl_sys::CURLE_OPERATION_TIMEDOUT))));
+            }
+        }
+
+        for (id, result) in finished {
+            if let Some(handle) = self.handles.remove(&id) {
+                let mut easy = self.multi.remove2(handle)?;
+
+                // Follow redirects and retry failed requests by sending the request again with the same handle.
+                match transport::prepare_resend(&mut easy, &result) {
+                    Ok(Some(delay)) => {
+                        self.retries.push((Instant::now() + delay, easy));
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        easy.get_ref().finish(Err(e));
+                        continue;
+                    }
+                }
+
+                // The transfer has stopped, but that does not mean it succeeded.
+                easy.get_mut().complete(result);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Abort all active transfers because of a fatal error in the agent.
+    fn fail_all(&mut self, error: Error) {
+        let message = error.to_string();
+
+        for (_, handle) in self.handles.drain() {
+            handle.get_ref().transfer().complete(Err(ErrorKind::Curl(message.clone()).into()));
+        }
+
+        for (_, easy) in self.retries.drain(..) {
+            easy.get_ref().transfer().complete(Err(ErrorKind::Curl(message.clone()).into()));
+        }
+    }
+}
+
+impl Drop for AgentThread {
+    fn drop(&mut self) {
+        // If the agent thread panics, such as when a callback provided by the user panics, fail any transfers that are
+        // still active so that nobody waits for them forever.
+        for (_, handle) in self.handles.drain() {
+            handle.get_ref().transfer().complete(Err(terminated()));
+        }
+
+        for (_, easy) in self.retries.drain(..) {
+            easy.get_ref().transfer().complete(Err(terminated()));
+        }
+
+        while let Ok(message) = self.receiver.try_recv() {
+            if let Message::Begin(easy) = message {
+                easy.get_ref().transfer().complete(Err(terminated()));
+            }
+        }
+    }
+}
+
+/// Create the error returned when the agent thread has stopped before a transfer could complete.
+fn terminated() -> Error {
+    ErrorKind::AgentFailed(String::from("agent thread terminated unexpectedly")).into()
+}
@@ -0,0 +1,128 @@
+use curl;
+use std::fmt;
+use super::*;
+
+
+/// Credentials for authenticating with a server, and the scheme to authenticate with.
+///
+/// Schemes that require more than one round trip to the server, such as Digest, NTLM and Negotiate, are handled
+/// automatically. Credentials are only ever sent to the host of the original request, and never to a host that a
+/// request is redirected to.
+#[derive(Clone)]
+pub enum Authentication {
+    /// HTTP Basic authentication. The password is sent in plain text, so this should only be used over HTTPS.
+    Basic {
+        username: String,
+        password: String,
+    },
+    /// HTTP Digest authentication.
+    Digest {
+        username: String,
+        password: String,
+    },
+    /// A bearer token, such as an OAuth 2.0 access token.
+    Bearer(String),
+    /// NTLM authentication.
+    Ntlm {
+        username: String,
+        password: String,
+    },
+    /// SPNEGO (Negotiate) authentication, using the Kerberos or Windows credentials of the current user.
+    Negotiate,
+    /// Use the most secure of the Basic, Digest, NTLM and Negotiate schemes that the server supports.
+    ///
+    /// This costs an extra round trip to find out which schemes the server supports.
+    Any {
+        username: String,
+        password: String,
+    },
+}
+
+impl Authentication {
+    /// Check that the authentication scheme is supported by the version of libcurl in use.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let version = curl::Version::get();
+
+        match self {
+            &Authentication::Ntlm { .. } if !version.feature_ntlm() => {
+                Err(ErrorKind::InvalidConfiguration(String::from("NTLM authentication is not supported by libcurl")).into())
+            }
+            &Authentication::Negotiate if !version.feature_spnego() => {
+                Err(ErrorKind::InvalidConfiguration(String::from("Negotiate authentication is not supported by libcurl")).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the schemes of authentication challenges that libcurl answers by sending the request again with
+    /// credentials, in lowercase.
+    pub(crate) fn challenge_schemes(&self) -> &'static [&'static str] {
+        match self {
+            &Authentication::Digest { .. } => &["digest"],
+            &Authentication::Ntlm { .. } => &["ntlm"],
+            &Authentication::Negotiate => &["negotiate"],
+            &Authentication::Any { .. } => &["basic", "digest", "ntlm", "negotiate"],
+            // Basic credentials and bearer tokens are sent with the first request.
+            _ => &[],
+        }
+    }
+
+    /// Configure an easy handle to authenticate with these credentials.
+    ///
+    /// Bearer tokens are not supported by all versions of libcurl, so an `Authorization` header is added to the given
+    /// request headers for them instead, unless the request already has one.
+    pub(crate) fn apply<H>(&self, easy: &mut curl::easy::Easy2<H>, headers: &mut http::HeaderMap) -> Result<(), Error> {
+        let mut auth = curl::easy::Auth::new();
+
+        let (username, password) = match self {
+            &Authentication::Basic { ref username, ref password } => {
+                auth.basic(true);
+                (username.as_str(), password.as_str())
+            }
+            &Authentication::Digest { ref username, ref password } => {
+                auth.digest(true);
+                (username.as_str(), password.as_str())
+            }
+            &Authentication::Bearer(ref token) => {
+                if !headers.contains_key(http::header::AUTHORIZATION) {
+                    let value = http::header::HeaderValue::from_str(&format!("Bearer {}", token)).map_err(http::Error::from)?;
+                    headers.insert(http::header::AUTHORIZATION, value);
+                }
+                return Ok(());
+            }
+            &Authentication::Ntlm { ref username, ref password } => {
+                auth.ntlm(true);
+                (username.as_str(), password.as_str())
+            }
+            &Authentication::Negotiate => {
+                // The credentials of the current user are used, but curl still requires a user name to be set.
+                auth.gssnegotiate(true);
+                ("", "")
+            }
+            &Authentication::Any { ref username, ref password } => {
+                auth.basic(true).digest(true).ntlm(true).gssnegotiate(true);
+                (username.as_str(), password.as_str())
+            }
+        };
+
+        easy.http_auth(&auth)?;
+        easy.username(username)?;
+        easy.password(password)?;
+
+        Ok(())
+    }
+}
+
+// Credentials are deliberately left out so that they do not end up in logs.
+impl fmt::Debug for Authentication {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            &Authentication::Basic { .. } => "Basic",
+            &Authentication::Digest { .. } => "Digest",
+            &Authentication::Bearer(_) => "Bearer",
+            &Authentication::Ntlm { .. } => "Ntlm",
+            &Authentication::Negotiate => "Negotiate",
+            &Authentication::Any { .. } => "Any",
+        })
+    }
+}
@@ -1,4 +1,6 @@
 use error::Error;
+use form;
+use multipart::Form;
 use std::fs::File;
 use std::io::{self, Cursor, Read, Seek, SeekFrom};
 
@@ -10,7 +12,24 @@ pub enum Body {
     /// A body stored as a byte array.
     Bytes(Cursor<Vec<u8>>),
     /// A body read from a stream.
+    ///
+    /// A streaming body can only be sent once, so requests with one fail if they have to be sent again, such as when
+    /// following a `307` or `308` redirect.
     Streaming(Box<Read + Send>),
+    /// A body read from a stream of a known size in bytes. Like a streaming body, it can only be sent once.
+    SizedStreaming(Box<Read + Send>, u64),
+    /// A body read from a regular file, starting at the position the file was at when the body was created.
+    ///
+    /// Unlike a streaming body, it is rewound to that position if it has to be sent again.
+    File {
+        file: File,
+        start: u64,
+        len: u64,
+    },
+    /// A `multipart/form-data` body.
+    Multipart(Form),
+    /// A body with a specific media type, which is sent as the `Content-Type` header unless the request sets one.
+    Typed(String, Box<Body>),
 }
 
 impl Body {
@@ -19,20 +38,70 @@ impl Body {
         Body::Streaming(Box::new(reader))
     }
 
+    /// Create a body from a reader that will produce exactly the given number of bytes.
+    ///
+    /// Unlike bodies of unknown size, the body is sent with a `Content-Length` header instead of using chunked
+    /// encoding.
+    pub fn from_reader_sized<R: Read + Send + 'static>(reader: R, len: u64) -> Body {
+        Body::SizedStreaming(Box::new(reader), len)
+    }
+
+    /// Create an `application/x-www-form-urlencoded` body from name-value pairs.
+    pub fn form<I, K, V>(pairs: I) -> Body
+        where I: IntoIterator<Item = (K, V)>,
+              K: AsRef<str>,
+              V: AsRef<str>
+    {
+        Body::from(form::encode(pairs)).with_content_type("application/x-www-form-urlencoded")
+    }
+
+    /// Set the media type of this body.
+    pub fn with_content_type<S: Into<String>>(self, content_type: S) -> Body {
+        match self {
+            Body::Typed(_, body) => Body::Typed(content_type.into(), body),
+            body => Body::Typed(content_type.into(), Box::new(body)),
+        }
+    }
+
     /// Report if this body is defined as empty.
     pub fn is_empty(&self) -> bool {
         match self {
             &Body::Empty => true,
+            &Body::Typed(_, ref body) => body.is_empty(),
             _ => false,
         }
     }
 
     /// Get the size of the body, if known.
-    pub fn len(&self) -> Option<usize> {
+    pub fn len(&self) -> Option<u64> {
         match self {
             &Body::Empty => Some(0),
-            &Body::Bytes(ref bytes) => Some(bytes.get_ref().len()),
+            &Body::Bytes(ref bytes) => Some(bytes.get_ref().len() as u64),
             &Body::Streaming(_) => None,
+            &Body::SizedStreaming(_, len) => Some(len),
+            &Body::File { len, .. } => Some(len),
+            &Body::Multipart(ref form) => form.len(),
+            &Body::Typed(_, ref body) => body.len(),
+        }
+    }
+
+    /// Get the media type of the body to send as the `Content-Type` header, if the body has a specific one.
+    pub fn content_type(&self) -> Option<String> {
+        match self {
+            &Body::Multipart(ref form) => Some(form.content_type()),
+            &Body::Typed(ref content_type, _) => Some(content_type.clone()),
+            _ => None,
+        }
+    }
+
+    /// Check if the body can be rewound to be sent again.
+    pub(crate) fn is_rewindable(&self) -> bool {
+        match self {
+            &Body::Streaming(_) => false,
+            &Body::SizedStreaming(..) => false,
+            &Body::Multipart(ref form) => form.is_rewindable(),
+            &Body::Typed(_, ref body) => body.is_rewindable(),
+            _ => true,
         }
     }
 
@@ -41,19 +110,35 @@ impl Body {
         match self {
             &mut Body::Empty => Ok(String::new()),
             &mut Body::Bytes(ref bytes) => String::from_utf8(bytes.get_ref().clone()).map_err(Into::into),
-            &mut Body::Streaming(ref mut reader) => {
+            &mut Body::Streaming(ref mut reader) | &mut Body::SizedStreaming(ref mut reader, _) => {
                 let mut string = String::new();
                 reader.read_to_string(&mut string)?;
                 Ok(string)
             },
+            &mut Body::File { ref mut file, .. } => {
+                let mut string = String::new();
+                file.read_to_string(&mut string)?;
+                Ok(string)
+            },
+            &mut Body::Multipart(ref mut form) => {
+                let mut string = String::new();
+                form.read_to_string(&mut string)?;
+                Ok(string)
+            },
+            &mut Body::Typed(_, ref mut body) => body.text(),
         }
     }
 
-    /// Attempt to parse the response as JSON.
+    /// Create a JSON body by serializing the given value. The body is sent with the `application/json` content type.
+    #[cfg(feature = "json")]
+    pub fn from_json<T: ::serde::Serialize>(value: &T) -> Result<Body, Error> {
+        Ok(Body::from(::serde_json::to_vec(value)?).with_content_type("application/json"))
+    }
+
+    /// Attempt to deserialize the response as JSON into a value of the given type.
     #[cfg(feature = "json")]
-    pub fn json(&mut self) -> Result<::json::JsonValue, Error> {
-        let text = self.text()?;
-        Ok(::json::parse(&text)?)
+    pub fn json<T: ::serde::de::DeserializeOwned>(&mut self) -> Result<T, Error> {
+        Ok(::serde_json::from_reader(self)?)
     }
 }
 
@@ -63,6 +148,10 @@ impl Read for Body {
             &mut Body::Empty => Ok(0),
             &mut Body::Bytes(ref mut bytes) => bytes.read(buf),
             &mut Body::Streaming(ref mut reader) => reader.read(buf),
+            &mut Body::SizedStreaming(ref mut reader, _) => reader.read(buf),
+            &mut Body::File { ref mut file, .. } => file.read(buf),
+            &mut Body::Multipart(ref mut form) => form.read(buf),
+            &mut Body::Typed(_, ref mut body) => body.read(buf),
         }
     }
 }
@@ -70,7 +159,19 @@ impl Read for Body {
 impl Seek for Body {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         match self {
+            &mut Body::Empty => Ok(0),
             &mut Body::Bytes(ref mut bytes) => bytes.seek(pos),
+            // Positions are relative to where the body starts in the file.
+            &mut Body::File { ref mut file, start, .. } => {
+                let pos = match pos {
+                    SeekFrom::Start(offset) => SeekFrom::Start(start + offset),
+                    pos => pos,
+                };
+                file.seek(pos).map(|position| position.saturating_sub(start))
+            }
+            // Forms can only be rewound to the start.
+            &mut Body::Multipart(ref mut form) if pos == SeekFrom::Start(0) => form.rewind().map(|_| 0),
+            &mut Body::Typed(_, ref mut body) => body.seek(pos),
             _ => Err(io::ErrorKind::InvalidInput.into()),
         }
     }
@@ -106,8 +207,26 @@ impl<'a> From<&'a str> for Body {
     }
 }
 
+impl From<Form> for Body {
+    fn from(body: Form) -> Body {
+        Body::Multipart(body)
+    }
+}
+
 impl From<File> for Body {
-    fn from(body: File) -> Body {
-        Body::Streaming(Box::new(body))
+    /// Create a body from a file. If it is a regular file, the size of the body is the remaining size of the file, and
+    /// the body can be rewound to the current position of the file.
+    fn from(mut body: File) -> Body {
+        let metadata = body.metadata();
+        let position = body.seek(SeekFrom::Current(0));
+
+        match (metadata, position) {
+            (Ok(ref metadata), Ok(position)) if metadata.is_file() => Body::File {
+                len: metadata.len().saturating_sub(position),
+                start: position,
+                file: body,
+            },
+            _ => Body::Streaming(Box::new(body)),
+        }
     }
 }
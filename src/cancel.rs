@@ -0,0 +1,86 @@
+use agent::{Agent, WeakAgent};
+use std::fmt;
+use std::mem;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use transport::Transfer;
+
+
+/// A token for cancelling requests from another thread.
+///
+/// To make a request cancellable, attach a clone of the token to it as an extension. Cancelling the token aborts every
+/// request it is attached to that is still in progress, and makes requests sent with it afterwards fail right away.
+/// Cancelled requests fail with `ErrorKind::Cancelled`, and so does reading the body of a response that is still
+/// being received.
+///
+/// ```rust
+/// use chttp::{Body, CancellationToken};
+/// use chttp::http::Request;
+///
+/// let token = CancellationToken::new();
+/// let request = Request::get("https://example.org")
+///     .extension(token.clone())
+///     .body(Body::Empty)
+///     .unwrap();
+///
+/// // Later, from any thread:
+/// token.cancel();
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    /// Transfers in progress that the token is attached to, and the agents executing them.
+    transfers: Mutex<Vec<(WeakAgent, Weak<Transfer>)>>,
+}
+
+impl CancellationToken {
+    /// Create a new token that has not been cancelled.
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancel all requests that this token is attached to.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+
+        let transfers = mem::replace(&mut *self.inner.transfers.lock().unwrap(), Vec::new());
+        for (agent, transfer) in transfers {
+            if let (Some(agent), Some(transfer)) = (agent.upgrade(), transfer.upgrade()) {
+                agent.cancel(transfer.id()).ok();
+            }
+        }
+    }
+
+    /// Check if this token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Attach this token to a transfer that has been handed to the given agent.
+    pub(crate) fn register(&self, agent: &Agent, transfer: &Arc<Transfer>) {
+        let mut transfers = self.inner.transfers.lock().unwrap();
+
+        // Forget about transfers that have already stopped.
+        transfers.retain(|&(_, ref transfer)| transfer.upgrade().map_or(false, |transfer| !transfer.is_complete()));
+
+        // The token may have been cancelled after the request was sent, but before we got here.
+        if self.is_cancelled() {
+            agent.cancel(transfer.id()).ok();
+        } else {
+            transfers.push((agent.downgrade(), Arc::downgrade(transfer)));
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
@@ -1,43 +1,55 @@
+use agent::Agent;
+use cookies::CookieJar;
+use middleware::Middleware;
+use futures::{Async, Future, Poll};
 use std::io;
 use std::io::Read;
-use std::sync::{Arc, Mutex, Weak};
-use transport::Transport;
+use std::mem;
+use share::Share;
+use std::sync::Arc;
+use transport::{self, Transfer};
 use super::*;
 
-const PRELOADED_TRANSPORTS: usize = 32;
-
 
 /// An HTTP client for making requests.
 ///
-/// The client maintains a connection pool internally and is expensive to create, so we recommend re-using your clients
-/// instead of discarding and recreating them.
+/// The client executes all of its requests on a single background thread that maintains a connection pool internally,
+/// so it is expensive to create. We recommend re-using your clients instead of discarding and recreating them.
 pub struct Client {
-    //max_connections: Option<u16>,
     options: Options,
-    transport_pool: Arc<Mutex<Vec<Transport>>>,
-    //transport_count: u16,
+    agent: Agent,
+    cookie_jar: CookieJar,
+    middleware: Arc<Vec<Arc<Middleware>>>,
 }
 
 impl Default for Client {
     fn default() -> Self {
-        let options: Options = Default::default();
-        let mut transport_pool = Vec::with_capacity(PRELOADED_TRANSPORTS);
-        for _ in 0..PRELOADED_TRANSPORTS {
-            transport_pool.push(Transport::with_options(options.clone()));
-        }
-        let transport_pool = Arc::new(Mutex::new(transport_pool));
-        Self { options, transport_pool }
+        Client::builder().build().expect("failed to create default client")
     }
 }
 
 impl Client {
+    /// Create a new builder for configuring a client.
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Create a new client with the given options.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the options are invalid or if the background thread for executing requests could not be started. Use
+    /// `Client::builder` to handle these errors instead.
     pub fn with_options(options: Options) -> Self {
-        let mut transport_pool = Vec::with_capacity(PRELOADED_TRANSPORTS);
-        for _ in 0..PRELOADED_TRANSPORTS {
-            transport_pool.push(Transport::with_options(options.clone()));
-        }
-        let transport_pool = Arc::new(Mutex::new(transport_pool));
-        Self { options, transport_pool }
+        Client::builder()
+            .options(options)
+            .build()
+            .expect("failed to create client")
+    }
+
+    /// Get the cookie jar used to store cookies for requests made by this client.
+    pub fn cookie_jar(&self) -> &CookieJar {
+        &self.cookie_jar
     }
 
     /// Sends a GET request.
@@ -65,72 +77,286 @@ impl Client {
     }
 
     /// Sends a request and returns the response.
+    ///
+    /// This blocks the current thread until the response header has been received.
     pub fn send(&self, request: Request) -> Result<Response, Error> {
-        if let Some(mut transport) = self.get_transport() {
-            let mut response = transport.execute(request)?;
-            let stream = self.create_stream(transport);
-
-            response
-                .body(Body::from_reader(stream))
-                .map_err(Into::into)
-        } else {
-            Err(Error::TooManyConnections)
+        self.send_async(request).wait()
+    }
+
+    /// Begin sending a request and return a future for the response.
+    ///
+    /// The request is executed on the client's background thread, so many requests can be in flight at once without
+    /// needing a thread for each one. The future resolves once the response header has been received.
+    ///
+    /// Any `Options` attached to the request as an extension take precedence over the client's options. A
+    /// `CancellationToken` attached to the request as an extension can be used to cancel it.
+    pub fn send_async(&self, mut request: Request) -> ResponseFuture {
+        // Run the request through the middleware until one of them short-circuits it.
+        for (i, middleware) in self.middleware.iter().enumerate() {
+            match middleware.before(&mut request) {
+                Ok(None) => {}
+                Ok(Some(response)) => return ResponseFuture {
+                    state: FutureState::Ready(response),
+                    middleware: self.middleware.clone(),
+                    entered: i + 1,
+                },
+                Err(e) => return ResponseFuture::failed(e.with_request(request.method(), request.uri())),
+            }
         }
+
+        let options = match request.extensions().get::<Options>() {
+            Some(options) => options.merge(&self.options),
+            None => self.options.clone(),
+        };
+
+        let method = request.method().clone();
+        let uri = request.uri().clone();
+
+        if let Err(e) = options.validate() {
+            return ResponseFuture::failed(e.with_request(&method, &uri));
+        }
+
+        let token = request.extensions().get::<CancellationToken>().cloned();
+        if token.as_ref().map_or(false, CancellationToken::is_cancelled) {
+            return ResponseFuture::failed(Error::from(ErrorKind::Cancelled).with_request(&method, &uri));
+        }
+
+        let (easy, transfer) = match transport::create_handle(request, &options) {
+            Ok(handle) => handle,
+            Err(e) => return ResponseFuture::failed(e.with_request(&method, &uri)),
+        };
+
+        match self.agent.begin(easy) {
+            Ok(()) => {
+                if let Some(token) = token {
+                    token.register(&self.agent, &transfer);
+                }
+
+                ResponseFuture {
+                    state: FutureState::Pending(transfer, self.agent.clone()),
+                    middleware: self.middleware.clone(),
+                    entered: self.middleware.len(),
+                }
+            }
+            Err(e) => ResponseFuture::failed(e.with_request(&method, &uri)),
+        }
+    }
+}
+
+/// An HTTP client builder, capable of creating custom `Client` instances with customized behavior.
+///
+/// All settings are validated when the client is built, so configuration mistakes are reported immediately instead of
+/// when a request is sent:
+///
+/// ```rust
+/// use chttp::{Client, Options};
+/// use std::time::Duration;
+///
+/// let client = Client::builder()
+///     .options(Options::default().with_timeout(Some(Duration::from_secs(60))))
+///     .max_connections(Some(4))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct ClientBuilder {
+    options: Options,
+    max_connections: Option<usize>,
+    max_connections_per_host: Option<usize>,
+    shared_cache: bool,
+    middleware: Vec<Arc<Middleware>>,
+}
+
+impl ClientBuilder {
+    /// Set the default options to use for requests sent by the client.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the maximum number of connections the client will have open at once, across all hosts.
+    ///
+    /// Requests that would need to open a connection beyond this limit wait until an existing connection becomes
+    /// available. Connections are pooled and re-used by scheme, host and port.
+    ///
+    /// The default value is `None` (unlimited).
+    pub fn max_connections(mut self, max: Option<usize>) -> Self {
+        self.max_connections = max;
+        self
     }
 
-    /// Note - this can no longer fail, as `max_connections` check disabled
+    /// Set the maximum number of connections the client will have open at once to a single host.
     ///
-    fn get_transport(&self) -> Option<Transport> {
-        let mut pool = self.transport_pool.lock().unwrap();
+    /// Requests that would need to open a connection beyond this limit wait until an existing connection to the same
+    /// host becomes available.
+    ///
+    /// The default value is `None` (unlimited).
+    pub fn max_connections_per_host(mut self, max: Option<usize>) -> Self {
+        self.max_connections_per_host = max;
+        self
+    }
+
+    /// Share a DNS cache and TLS session cache between all requests made by the client.
+    ///
+    /// When enabled, host names only need to be resolved once, and new connections to a host the client has talked to
+    /// before can resume an earlier TLS session instead of performing a full handshake. Open connections are always
+    /// pooled and shared between requests made by the same client regardless of this option.
+    ///
+    /// The default value is `false`.
+    pub fn shared_cache(mut self, enable: bool) -> Self {
+        self.shared_cache = enable;
+        self
+    }
+
+    /// Add a middleware to run around every request sent by the client.
+    ///
+    /// Middleware runs in the order it is added before a request is sent, and in reverse order once the response is
+    /// received.
+    pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Build a client with the configured settings.
+    ///
+    /// Returns an error if any of the settings are invalid, or if the background thread for executing requests could
+    /// not be started.
+    pub fn build(self) -> Result<Client, Error> {
+        self.options.validate()?;
+
+        if self.max_connections == Some(0) || self.max_connections_per_host == Some(0) {
+            return Err(ErrorKind::InvalidConfiguration(String::from("connection limits must be greater than zero")).into());
+        }
+
+        // Every client has its own cookie jar, which is stored in a share handle used by all of its transfers.
+        let mut share = Share::new()?;
+        share.share_cookies()?;
+
+        if self.shared_cache {
+            share.share_dns()?;
 
-        if let Some(transport) = pool.pop() {
-            return Some(transport);
+            // Not all TLS backends support session sharing, and a missing cache should not prevent requests.
+            if let Err(e) = share.share_ssl_sessions() {
+                warn!("TLS sessions cannot be shared: {}", e);
+            }
         }
 
-        // if let Some(max) = self.max_connections {
-        //     if self.transport_count >= max {
-        //         return None;
-        //     }
-        // }
+        let share = Arc::new(share);
+        let agent = Agent::new(self.max_connections, self.max_connections_per_host, share.clone())?;
 
-        Some(self.create_transport())
+        Ok(Client {
+            options: self.options,
+            agent: agent,
+            cookie_jar: CookieJar::new(share),
+            middleware: Arc::new(self.middleware),
+        })
     }
+}
+
+/// A future for a response to a request sent with `Client::send_async`.
+///
+/// Dropping the future before it resolves aborts the request.
+pub struct ResponseFuture {
+    state: FutureState,
+    /// The client's middleware.
+    middleware: Arc<Vec<Arc<Middleware>>>,
+    /// The number of middleware that saw the request, and should see the response.
+    entered: usize,
+}
 
-    fn create_transport(&self) -> Transport {
-        Transport::with_options(self.options.clone())
+enum FutureState {
+    /// The request could not be started.
+    Failed(Error),
+    /// The request is in progress.
+    Pending(Arc<Transfer>, Agent),
+    /// A response was provided by middleware instead of sending the request.
+    Ready(Response),
+    /// The future has already resolved.
+    Done,
+}
+
+impl ResponseFuture {
+    fn failed(error: Error) -> Self {
+        ResponseFuture {
+            state: FutureState::Failed(error),
+            middleware: Arc::new(Vec::new()),
+            entered: 0,
+        }
+    }
+
+    /// Run a response through the middleware that saw the request, in reverse order.
+    fn after(&self, mut response: Response) -> Result<Response, Error> {
+        for middleware in self.middleware[..self.entered].iter().rev() {
+            middleware.after(&mut response)?;
+        }
+
+        Ok(response)
     }
+}
+
+impl Future for ResponseFuture {
+    type Item = Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Response, Error> {
+        match mem::replace(&mut self.state, FutureState::Done) {
+            FutureState::Failed(e) => Err(e),
+            FutureState::Pending(transfer, agent) => match transfer.poll_response()? {
+                Async::Ready(mut builder) => {
+                    let stream = Stream {
+                        transfer: transfer,
+                        agent: agent,
+                    };
 
-    fn create_stream(&self, transport: Transport) -> Stream {
-        Stream {
-            pool: Arc::downgrade(&self.transport_pool),
-            transport: Some(transport),
+                    let response = builder.body(Body::from_reader(stream))?;
+                    Ok(Async::Ready(self.after(response)?))
+                }
+                Async::NotReady => {
+                    self.state = FutureState::Pending(transfer, agent);
+                    Ok(Async::NotReady)
+                }
+            },
+            FutureState::Ready(response) => Ok(Async::Ready(self.after(response)?)),
+            FutureState::Done => panic!("response future polled after completion"),
+        }
+    }
+}
+
+impl Drop for ResponseFuture {
+    fn drop(&mut self) {
+        if let FutureState::Pending(ref transfer, ref agent) = self.state {
+            agent.cancel(transfer.id()).ok();
         }
     }
 }
 
 /// Stream that reads the response body incrementally.
 ///
-/// A stream object will hold on to the connection that initiated the request until the entire response is read or the
+/// A stream object will hold on to the transfer that initiated the request until the entire response is read or the
 /// stream is dropped.
 struct Stream {
-    pool: Weak<Mutex<Vec<Transport>>>,
-    transport: Option<Transport>,
+    transfer: Arc<Transfer>,
+    agent: Agent,
 }
 
 impl Read for Stream {
     fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
-        self.transport.as_mut().unwrap().read(buffer)
+        let (len, unpause) = self.transfer.read(buffer)?;
+
+        // Now that there is room in the buffer again, resume the transfer if we had to pause it.
+        if unpause {
+            self.agent.unpause(self.transfer.id())?;
+        }
+
+        Ok(len)
     }
 }
 
 impl Drop for Stream {
     fn drop(&mut self) {
-        if let Some(transport) = self.transport.take() {
-            if let Some(pool) = self.pool.upgrade() {
-                pool.lock()
-                    .unwrap()
-                    .push(transport);
-            }
+        // Abort the transfer if the rest of the response is no longer wanted.
+        if !self.transfer.is_complete() {
+            self.agent.cancel(self.transfer.id()).ok();
         }
     }
 }
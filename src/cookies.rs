@@ -0,0 +1,187 @@
+use curl;
+use share::Share;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::str;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use super::*;
+
+
+/// Prefix curl uses for HTTP-only cookies in the Netscape cookie file format.
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+
+/// An in-memory cookie store belonging to a client.
+///
+/// Cookies set by servers are stored in the jar automatically, and are sent along with any later requests made by the
+/// same client that they apply to. Each client has its own cookie jar, so cookies are never shared between clients.
+///
+/// Cookies can be saved to and loaded from a file in the Netscape cookie file format, which is also used by curl and
+/// most browsers, so that sessions can survive a restart.
+pub struct CookieJar {
+    share: Arc<Share>,
+}
+
+impl CookieJar {
+    pub(crate) fn new(share: Arc<Share>) -> CookieJar {
+        CookieJar {
+            share: share,
+        }
+    }
+
+    /// Get a list of all cookies currently in the jar.
+    pub fn cookies(&self) -> Result<Vec<Cookie>, Error> {
+        let mut easy = self.handle()?;
+
+        Ok(easy.cookies()?
+            .iter()
+            .filter_map(|line| str::from_utf8(line).ok())
+            .filter_map(Cookie::parse)
+            .collect())
+    }
+
+    /// Remove all cookies from the jar.
+    pub fn clear(&self) -> Result<(), Error> {
+        self.handle()?.cookie_list("ALL")?;
+        Ok(())
+    }
+
+    /// Load cookies from a file in the Netscape cookie file format into the jar.
+    ///
+    /// Cookies already in the jar are kept, unless they are replaced by a cookie in the file with the same name, domain
+    /// and path.
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let file = BufReader::new(File::open(path)?);
+        let mut easy = self.handle()?;
+
+        for line in file.lines() {
+            let line = line?;
+
+            // Skip blank lines and comments, but not HTTP-only cookies, which look like comments.
+            if line.trim().is_empty() || (line.starts_with('#') && !line.starts_with(HTTP_ONLY_PREFIX)) {
+                continue;
+            }
+
+            easy.cookie_list(&line)?;
+        }
+
+        Ok(())
+    }
+
+    /// Save all cookies in the jar to a file in the Netscape cookie file format, replacing the file if it exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut easy = self.handle()?;
+        let mut file = File::create(path)?;
+
+        writeln!(file, "# Netscape HTTP Cookie File")?;
+
+        for line in easy.cookies()?.iter() {
+            file.write_all(line)?;
+            file.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a temporary easy handle for manipulating the cookies in the share handle.
+    fn handle(&self) -> Result<curl::easy::Easy, Error> {
+        let easy = curl::easy::Easy::new();
+
+        unsafe {
+            self.share.attach(easy.raw())?;
+        }
+
+        Ok(easy)
+    }
+}
+
+
+/// A single HTTP cookie.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    include_subdomains: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    /// Get the name of the cookie.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the value of the cookie.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Get the domain the cookie applies to.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Check if the cookie also applies to subdomains of its domain.
+    pub fn include_subdomains(&self) -> bool {
+        self.include_subdomains
+    }
+
+    /// Get the path the cookie applies to.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Check if the cookie may only be sent over secure connections.
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+
+    /// Check if the cookie is hidden from scripts in a browser.
+    pub fn is_http_only(&self) -> bool {
+        self.http_only
+    }
+
+    /// Get the time the cookie expires, or `None` if it is a session cookie.
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.expires
+    }
+
+    /// Parse a cookie from a line in the Netscape cookie file format.
+    fn parse(line: &str) -> Option<Cookie> {
+        let (line, http_only) = if line.starts_with(HTTP_ONLY_PREFIX) {
+            (&line[HTTP_ONLY_PREFIX.len()..], true)
+        } else {
+            (line, false)
+        };
+
+        let mut fields = line.split('\t');
+
+        let domain = fields.next()?;
+        let include_subdomains = fields.next()? == "TRUE";
+        let path = fields.next()?;
+        let secure = fields.next()? == "TRUE";
+        let expires = fields.next()?.parse::<u64>().ok()?;
+        let name = fields.next()?;
+        let value = fields.next().unwrap_or("");
+
+        Some(Cookie {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            domain: domain.to_owned(),
+            include_subdomains: include_subdomains,
+            path: path.to_owned(),
+            secure: secure,
+            http_only: http_only,
+            expires: match expires {
+                0 => None,
+                seconds => Some(UNIX_EPOCH + Duration::from_secs(seconds)),
+            },
+        })
+    }
+}
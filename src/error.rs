@@ -1,25 +1,174 @@
 use curl;
+use curl_sys;
 use http;
 use std::error::Error as StdError;
 use std::fmt;
 use std::io;
 
 
-/// All possible types of errors that can be returned from cHTTP.
+/// An error that occurred while sending a request or receiving its response.
+///
+/// Besides the kind of error, an error records which request failed, the phase of the transfer it failed in, and the
+/// underlying libcurl error code, whenever they are known. The error that caused it, if any, is available through
+/// `source`.
+pub struct Error(Box<Inner>);
+
+/// The contents of an error, kept behind a pointer so that results carrying an error stay small.
+#[derive(Debug)]
+struct Inner {
+    kind: ErrorKind,
+    method: Option<http::Method>,
+    uri: Option<http::Uri>,
+    phase: Option<Phase>,
+    curl_code: Option<i32>,
+    source: Option<Box<StdError + Send + Sync>>,
+}
+
+impl Error {
+    /// Get the kind of error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.0.kind
+    }
+
+    /// Get the method of the request that failed, if known.
+    pub fn method(&self) -> Option<&http::Method> {
+        self.0.method.as_ref()
+    }
+
+    /// Get the URI of the request that failed, if known. If redirects were followed, this is the URI of the request
+    /// that was being sent when the error occurred.
+    pub fn uri(&self) -> Option<&http::Uri> {
+        self.0.uri.as_ref()
+    }
+
+    /// Get the phase of the transfer that the error occurred in, if known.
+    pub fn phase(&self) -> Option<Phase> {
+        self.0.phase
+    }
+
+    /// Get the libcurl error code that caused this error, if any.
+    pub fn curl_code(&self) -> Option<i32> {
+        self.0.curl_code
+    }
+
+    /// Record the request that failed, unless one has been recorded already.
+    pub(crate) fn with_request(mut self, method: &http::Method, uri: &http::Uri) -> Error {
+        if self.0.method.is_none() && self.0.uri.is_none() {
+            self.0.method = Some(method.clone());
+            self.0.uri = Some(uri.clone());
+        }
+        self
+    }
+
+    /// Record the phase of the transfer that the error occurred in, unless one has been recorded already.
+    pub(crate) fn with_phase(mut self, phase: Phase) -> Error {
+        self.0.phase = self.0.phase.or(Some(phase));
+        self
+    }
+
+    /// Change the kind of error, keeping its context.
+    pub(crate) fn with_kind(mut self, kind: ErrorKind) -> Error {
+        self.0.kind = kind;
+        self
+    }
+
+    fn with_source<E: StdError + Send + Sync + 'static>(mut self, source: E) -> Error {
+        self.0.source = Some(Box::new(source));
+        self
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Error")
+            .field("kind", &self.0.kind)
+            .field("method", &self.0.method)
+            .field("uri", &self.0.uri)
+            .field("phase", &self.0.phase)
+            .field("curl_code", &self.0.curl_code)
+            .field("source", &self.0.source)
+            .finish()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.kind)?;
+
+        let mut context = Vec::new();
+        if let (Some(method), Some(uri)) = (self.0.method.as_ref(), self.0.uri.as_ref()) {
+            context.push(format!("{} {}", method, uri));
+        }
+        if let Some(phase) = self.0.phase {
+            context.push(format!("while {}", phase));
+        }
+        if let Some(code) = self.0.curl_code {
+            context.push(format!("curl error {}", code));
+        }
+
+        if !context.is_empty() {
+            write!(f, " ({})", context.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        self.0.kind.message()
+    }
+
+    fn source(&self) -> Option<&(StdError + 'static)> {
+        match self.0.kind {
+            ErrorKind::InvalidHttpFormat(ref e) => Some(e),
+            ErrorKind::Io(ref e) => Some(e),
+            _ => self.0.source.as_ref().map(|e| &**e as &(StdError + 'static)),
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error(Box::new(Inner {
+            kind: kind,
+            method: None,
+            uri: None,
+            phase: None,
+            curl_code: None,
+            source: None,
+        }))
+    }
+}
+
+
+/// All possible kinds of errors that can be returned from cHTTP.
 #[derive(Debug)]
-pub enum Error {
+pub enum ErrorKind {
+    /// The request was aborted by a progress callback, or because a callback panicked.
+    Aborted,
+    /// The background thread that executes requests could not be started, or stopped before the request completed.
+    AgentFailed(String),
     /// A problem occurred with the local certificate.
     BadClientCertificate(Option<String>),
     /// The server certificate could not be validated.
     BadServerCertificate(Option<String>),
+    /// The request was cancelled with a `CancellationToken`.
+    Cancelled,
     /// Failed to connect to the server.
     ConnectFailed,
+    /// Connecting to the server took longer than the configured connect timeout.
+    ConnectTimeout,
     /// Couldn't resolve host name.
     CouldntResolveHost,
     /// Couldn't resolve proxy host name.
     CouldntResolveProxy,
     /// An unrecognized error thrown by libcurl.
     Curl(String),
+    /// The client was configured with invalid or unsupported options.
+    InvalidConfiguration(String),
+    /// The response text is not valid in its charset, or the charset is not supported.
+    InvalidCharset(String),
     /// Unrecognized or bad content encoding returned by the server.
     InvalidContentEncoding(Option<String>),
     /// Provided credentials were rejected by the server.
@@ -27,15 +176,28 @@ pub enum Error {
     /// Validation error when constructing the request or parsing the response.
     InvalidHttpFormat(http::Error),
     /// JSON syntax error when constructing or parsing JSON values.
-    InvalidJson,
+    ///
+    /// The line and column are 1-based positions in the JSON text where the error was found, and are zero if the error
+    /// did not happen while parsing, such as when a value could not be serialized.
+    InvalidJson {
+        line: usize,
+        column: usize,
+        message: String,
+    },
     /// Invalid UTF-8 string error.
     InvalidUtf8,
     /// An unknown I/O error.
     Io(io::Error),
+    /// The server did not send any of the response within the configured first byte timeout.
+    FirstByteTimeout,
+    /// The server responded with a client error or server error status. Returned by `error_for_status`.
+    HttpStatus(Box<HttpStatusError>),
     /// The server did not send a response.
     NoResponse,
     /// The server does not support or accept range requests.
     RangeRequestUnsupported,
+    /// No data was sent or received for longer than the configured read timeout.
+    ReadTimeout,
     /// An error occurred while writing the request body.
     RequestBodyError(Option<String>),
     /// An error occurred while reading the response body.
@@ -46,139 +208,242 @@ pub enum Error {
     SSLEngineError(Option<String>),
     /// An ongoing request took longer than the configured timeout time.
     Timeout,
-    /// Returned when making more simultaneous requests would exceed the configured TCP connection limit.
-    TooManyConnections,
     /// Number of redirects hit the maximum amount.
     TooManyRedirects,
-    /// An attempt was made to re-use a transport for a new request that already has another request in progress.
-    TransportBusy,
 }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}: {}", self, Error::description(self))
+impl ErrorKind {
+    /// Check if this is any of the kinds of timeout.
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            &ErrorKind::ConnectTimeout | &ErrorKind::FirstByteTimeout | &ErrorKind::ReadTimeout | &ErrorKind::Timeout => true,
+            _ => false,
+        }
     }
-}
 
-impl StdError for Error {
-    fn description(&self) -> &str {
+    fn message(&self) -> &str {
         match self {
-            &Error::BadClientCertificate(Some(ref e)) => e,
-            &Error::BadServerCertificate(Some(ref e)) => e,
-            &Error::ConnectFailed => "failed to connect to the server",
-            &Error::CouldntResolveHost => "couldn't resolve host name",
-            &Error::CouldntResolveProxy => "couldn't resolve proxy host name",
-            &Error::Curl(ref e) => e,
-            &Error::InvalidContentEncoding(Some(ref e)) => e,
-            &Error::InvalidCredentials => "credentials were rejected by the server",
-            &Error::InvalidHttpFormat(ref e) => e.description(),
-            &Error::InvalidJson => "body is not valid JSON",
-            &Error::InvalidUtf8 => "bytes are not valid UTF-8",
-            &Error::Io(ref e) => e.description(),
-            &Error::NoResponse => "server did not send a response",
-            &Error::RangeRequestUnsupported => "server does not support or accept range requests",
-            &Error::RequestBodyError(Some(ref e)) => e,
-            &Error::ResponseBodyError(Some(ref e)) => e,
-            &Error::SSLConnectFailed(Some(ref e)) => e,
-            &Error::SSLEngineError(Some(ref e)) => e,
-            &Error::Timeout => "request took longer than the configured timeout",
-            &Error::TooManyConnections => "max connection limit exceeded",
-            &Error::TooManyRedirects => "max redirect limit exceeded",
-            &Error::TransportBusy => "transport is already in use",
+            &ErrorKind::Aborted => "request was aborted by a callback",
+            &ErrorKind::AgentFailed(ref e) => e,
+            &ErrorKind::BadClientCertificate(Some(ref e)) => e,
+            &ErrorKind::BadServerCertificate(Some(ref e)) => e,
+            &ErrorKind::Cancelled => "request was cancelled",
+            &ErrorKind::ConnectFailed => "failed to connect to the server",
+            &ErrorKind::ConnectTimeout => "connecting to the server took longer than the configured timeout",
+            &ErrorKind::CouldntResolveHost => "couldn't resolve host name",
+            &ErrorKind::CouldntResolveProxy => "couldn't resolve proxy host name",
+            &ErrorKind::Curl(ref e) => e,
+            &ErrorKind::InvalidConfiguration(ref e) => e,
+            &ErrorKind::InvalidCharset(ref e) => e,
+            &ErrorKind::InvalidContentEncoding(Some(ref e)) => e,
+            &ErrorKind::InvalidCredentials => "credentials were rejected by the server",
+            &ErrorKind::InvalidHttpFormat(ref e) => e.description(),
+            &ErrorKind::InvalidJson { ref message, .. } => message,
+            &ErrorKind::InvalidUtf8 => "bytes are not valid UTF-8",
+            &ErrorKind::Io(ref e) => e.description(),
+            &ErrorKind::FirstByteTimeout => "server did not start responding within the configured timeout",
+            &ErrorKind::HttpStatus(_) => "server responded with an error status",
+            &ErrorKind::NoResponse => "server did not send a response",
+            &ErrorKind::RangeRequestUnsupported => "server does not support or accept range requests",
+            &ErrorKind::ReadTimeout => "no data was transferred for longer than the configured timeout",
+            &ErrorKind::RequestBodyError(Some(ref e)) => e,
+            &ErrorKind::ResponseBodyError(Some(ref e)) => e,
+            &ErrorKind::SSLConnectFailed(Some(ref e)) => e,
+            &ErrorKind::SSLEngineError(Some(ref e)) => e,
+            &ErrorKind::Timeout => "request took longer than the configured timeout",
+            &ErrorKind::TooManyRedirects => "max redirect limit exceeded",
             _ => "unknown error",
         }
     }
+}
 
-    fn cause(&self) -> Option<&StdError> {
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            &Error::InvalidHttpFormat(ref e) => Some(e),
-            &Error::Io(ref e) => Some(e),
-            _ => None,
+            // Leave out the headers and body, which can be long.
+            &ErrorKind::HttpStatus(ref error) => write!(f, "HttpStatus: server responded with status {}", error.status),
+            _ => write!(f, "{:?}: {}", self, self.message()),
         }
     }
 }
 
+
+/// A response with a client error or server error status, as described by `ErrorKind::HttpStatus`.
+#[derive(Debug)]
+pub struct HttpStatusError {
+    status: http::StatusCode,
+    headers: http::HeaderMap,
+    body: String,
+}
+
+impl HttpStatusError {
+    pub(crate) fn new(status: http::StatusCode, headers: http::HeaderMap, body: String) -> HttpStatusError {
+        HttpStatusError {
+            status: status,
+            headers: headers,
+            body: body,
+        }
+    }
+
+    /// Get the status of the response.
+    pub fn status(&self) -> http::StatusCode {
+        self.status
+    }
+
+    /// Get the headers of the response.
+    pub fn headers(&self) -> &http::HeaderMap {
+        &self.headers
+    }
+
+    /// Get the start of the response body, decoded as text.
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+}
+
+
+/// The phases of a transfer, used to describe where a request failed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Phase {
+    /// Resolving the host name of the server.
+    NameLookup,
+    /// Connecting to the server.
+    Connect,
+    /// Performing the TLS handshake with the server.
+    TlsHandshake,
+    /// Sending the request header and body.
+    SendRequest,
+    /// Waiting for the response header.
+    AwaitResponse,
+    /// Receiving the response body.
+    ReadBody,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Phase::NameLookup => "resolving host name",
+            Phase::Connect => "connecting",
+            Phase::TlsHandshake => "performing TLS handshake",
+            Phase::SendRequest => "sending request",
+            Phase::AwaitResponse => "waiting for response",
+            Phase::ReadBody => "reading response body",
+        })
+    }
+}
+
 impl From<curl::Error> for Error {
     fn from(error: curl::Error) -> Error {
-        if error.is_ssl_certproblem() || error.is_ssl_cacert_badfile() {
-            Error::BadClientCertificate(error.extra_description().map(str::to_owned))
-        } else if error.is_peer_failed_verification() || error.is_ssl_cacert() {
-            Error::BadServerCertificate(error.extra_description().map(str::to_owned))
+        let extra = error.extra_description().map(str::to_owned);
+
+        let (kind, phase) = if error.is_ssl_certproblem() || error.is_ssl_cacert_badfile() {
+            (ErrorKind::BadClientCertificate(extra), Some(Phase::TlsHandshake))
+        } else if error.is_peer_failed_verification() || error.is_ssl_cacert() || error.is_ssl_issuer_error() || error.code() == curl_sys::CURLE_SSL_PINNEDPUBKEYNOTMATCH {
+            (ErrorKind::BadServerCertificate(extra), Some(Phase::TlsHandshake))
         } else if error.is_couldnt_connect() {
-            Error::ConnectFailed
+            (ErrorKind::ConnectFailed, Some(Phase::Connect))
         } else if error.is_couldnt_resolve_host() {
-            Error::CouldntResolveHost
+            (ErrorKind::CouldntResolveHost, Some(Phase::NameLookup))
         } else if error.is_couldnt_resolve_proxy() {
-            Error::CouldntResolveProxy
+            (ErrorKind::CouldntResolveProxy, Some(Phase::NameLookup))
         } else if error.is_bad_content_encoding() || error.is_conv_failed() {
-            Error::InvalidContentEncoding(error.extra_description().map(str::to_owned))
+            (ErrorKind::InvalidContentEncoding(extra), None)
         } else if error.is_login_denied() {
-            Error::InvalidCredentials
+            (ErrorKind::InvalidCredentials, None)
         } else if error.is_got_nothing() {
-            Error::NoResponse
+            (ErrorKind::NoResponse, Some(Phase::AwaitResponse))
         } else if error.is_range_error() {
-            Error::RangeRequestUnsupported
+            (ErrorKind::RangeRequestUnsupported, None)
+        } else if error.is_send_fail_rewind() {
+            (ErrorKind::RequestBodyError(Some(String::from("request body is a stream that cannot be rewound to be sent again"))), Some(Phase::SendRequest))
         } else if error.is_read_error() || error.is_aborted_by_callback() {
-            Error::RequestBodyError(error.extra_description().map(str::to_owned))
+            (ErrorKind::RequestBodyError(extra), Some(Phase::SendRequest))
         } else if error.is_write_error() || error.is_partial_file() {
-            Error::ResponseBodyError(error.extra_description().map(str::to_owned))
+            (ErrorKind::ResponseBodyError(extra), Some(Phase::ReadBody))
         } else if error.is_ssl_connect_error() {
-            Error::SSLConnectFailed(error.extra_description().map(str::to_owned))
+            (ErrorKind::SSLConnectFailed(extra), Some(Phase::TlsHandshake))
         } else if error.is_ssl_engine_initfailed() || error.is_ssl_engine_notfound() || error.is_ssl_engine_setfailed() {
-            Error::SSLEngineError(error.extra_description().map(str::to_owned))
+            (ErrorKind::SSLEngineError(extra), None)
         } else if error.is_operation_timedout() {
-            Error::Timeout
+            (ErrorKind::Timeout, None)
         } else {
-            Error::Curl(error.description().to_owned())
-        }
+            (ErrorKind::Curl(error.description().to_owned()), None)
+        };
+
+        let code = error.code();
+        let mut error = Error::from(kind).with_source(error);
+        error.0.phase = phase;
+        error.0.curl_code = Some(code as i32);
+        error
     }
 }
 
 impl From<curl::MultiError> for Error {
     fn from(error: curl::MultiError) -> Error {
-        Error::Curl(error.description().to_owned())
+        Error::from(ErrorKind::Curl(error.description().to_owned())).with_source(error)
+    }
+}
+
+impl From<curl::ShareError> for Error {
+    fn from(error: curl::ShareError) -> Error {
+        Error::from(ErrorKind::Curl(error.description().to_owned())).with_source(error)
     }
 }
 
 impl From<http::Error> for Error {
     fn from(error: http::Error) -> Error {
-        Error::InvalidHttpFormat(error)
+        ErrorKind::InvalidHttpFormat(error).into()
     }
 }
 
 impl From<io::Error> for Error {
     fn from(error: io::Error) -> Error {
+        // Unwrap errors of ours that were passed through an I/O error, such as when reading a response body.
+        if error.get_ref().map_or(false, |inner| inner.is::<Error>()) {
+            return *error.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
+
         match error.kind() {
-            io::ErrorKind::ConnectionRefused => Error::ConnectFailed,
-            io::ErrorKind::TimedOut => Error::Timeout,
-            _ => Error::Io(error),
+            io::ErrorKind::ConnectionRefused => Error::from(ErrorKind::ConnectFailed).with_source(error),
+            io::ErrorKind::TimedOut => Error::from(ErrorKind::Timeout).with_source(error),
+            _ => ErrorKind::Io(error).into(),
         }
     }
 }
 
 impl From<Error> for io::Error {
     fn from(error: Error) -> io::Error {
-        match error {
-            Error::ConnectFailed => io::ErrorKind::ConnectionRefused.into(),
-            Error::Io(e) => e,
-            Error::Timeout => io::ErrorKind::TimedOut.into(),
-            _ => io::ErrorKind::Other.into()
-        }
+        let kind = match error.0.kind {
+            ErrorKind::ConnectFailed => io::ErrorKind::ConnectionRefused,
+            ErrorKind::Io(ref e) => e.kind(),
+            ref kind if kind.is_timeout() => io::ErrorKind::TimedOut,
+            _ => io::ErrorKind::Other,
+        };
+
+        // Keep the original error, so that it can be recovered when converted back.
+        io::Error::new(kind, error)
     }
 }
 
 impl From<::std::string::FromUtf8Error> for Error {
-    fn from(_: ::std::string::FromUtf8Error) -> Error {
-        Error::InvalidUtf8
+    fn from(error: ::std::string::FromUtf8Error) -> Error {
+        Error::from(ErrorKind::InvalidUtf8).with_source(error)
     }
 }
 
 #[cfg(feature = "json")]
-impl From<::json::Error> for Error {
-    fn from(error: ::json::Error) -> Error {
-        match error {
-            ::json::Error::FailedUtf8Parsing => Error::InvalidUtf8,
-            _ => Error::InvalidJson,
+impl From<::serde_json::Error> for Error {
+    fn from(error: ::serde_json::Error) -> Error {
+        if error.is_io() {
+            return io::Error::from(error).into();
         }
+
+        let kind = ErrorKind::InvalidJson {
+            line: error.line(),
+            column: error.column(),
+            message: error.to_string(),
+        };
+
+        Error::from(kind).with_source(error)
     }
 }
@@ -0,0 +1,75 @@
+use std::fmt::Write;
+
+
+/// Encode name-value pairs in the `application/x-www-form-urlencoded` format, as used by HTML forms and query strings.
+///
+/// ```rust
+/// assert_eq!(chttp::form::encode(vec![("q", "rust http"), ("page", "2")]), "q=rust+http&page=2");
+/// ```
+pub fn encode<I, K, V>(pairs: I) -> String
+    where I: IntoIterator<Item = (K, V)>,
+          K: AsRef<str>,
+          V: AsRef<str>
+{
+    let mut encoded = String::new();
+
+    for (name, value) in pairs {
+        if !encoded.is_empty() {
+            encoded.push('&');
+        }
+
+        encode_component(&mut encoded, name.as_ref());
+        encoded.push('=');
+        encode_component(&mut encoded, value.as_ref());
+    }
+
+    encoded
+}
+
+/// Append name-value pairs to the query string of a URI, for use with the convenience methods of `Client`.
+///
+/// ```rust
+/// let uri = chttp::form::append_query("https://example.org/search?lang=en", vec![("q", "rust http")]);
+///
+/// assert_eq!(uri, "https://example.org/search?lang=en&q=rust+http");
+/// ```
+pub fn append_query<I, K, V>(uri: &str, pairs: I) -> String
+    where I: IntoIterator<Item = (K, V)>,
+          K: AsRef<str>,
+          V: AsRef<str>
+{
+    let query = encode(pairs);
+    if query.is_empty() {
+        return uri.to_owned();
+    }
+
+    // The query goes before the fragment, if there is one.
+    let (base, fragment) = match uri.find('#') {
+        Some(index) => uri.split_at(index),
+        None => (uri, ""),
+    };
+
+    let separator = if !base.contains('?') {
+        "?"
+    } else if base.ends_with('?') || base.ends_with('&') {
+        ""
+    } else {
+        "&"
+    };
+
+    format!("{}{}{}{}", base, separator, query, fragment)
+}
+
+/// Percent-encode a name or value, encoding spaces as `+`.
+fn encode_component(dst: &mut String, value: &str) {
+    for byte in value.bytes() {
+        match byte {
+            b'*' | b'-' | b'.' | b'_' => dst.push(byte as char),
+            _ if byte.is_ascii_alphanumeric() => dst.push(byte as char),
+            b' ' => dst.push('+'),
+            _ => {
+                write!(dst, "%{:02X}", byte).unwrap();
+            }
+        }
+    }
+}
@@ -1,23 +1,47 @@
 extern crate curl;
+extern crate curl_sys;
+extern crate encoding_rs;
+pub extern crate futures;
 pub extern crate http;
-#[cfg(feature = "json")]
-extern crate json;
 #[macro_use]
 extern crate log;
 extern crate ringtail;
+#[cfg(feature = "json")]
+extern crate serde;
+#[cfg(feature = "json")]
+extern crate serde_json;
 
 pub use curl::Version;
 
+mod agent;
+pub mod auth;
 pub mod body;
+pub mod cancel;
 pub mod client;
+pub mod cookies;
 pub mod error;
+pub mod form;
+pub mod middleware;
+pub mod multipart;
 pub mod options;
+pub mod response;
+pub mod retry;
+mod share;
+pub mod ssl;
 mod transport;
+mod util;
 
+pub use auth::Authentication;
 pub use body::Body;
-pub use client::Client;
-pub use error::Error;
+pub use cancel::CancellationToken;
+pub use client::{Client, ClientBuilder};
+pub use cookies::{Cookie, CookieJar};
+pub use error::{Error, ErrorKind, HttpStatusError, Phase};
+pub use middleware::Middleware;
 pub use options::*;
+pub use response::{Metrics, ResponseBodyExt, ResponseExt, Trailers};
+pub use retry::RetryPolicy;
+pub use ssl::{ClientCertificate, SslVersion};
 
 
 pub type Request = http::Request<Body>;
@@ -0,0 +1,27 @@
+use super::*;
+
+
+/// Hooks that run around every request sent by a client, for concerns such as tracing, request signing or logging.
+///
+/// Middleware is registered on a `ClientBuilder` and runs in the order it was registered before a request is sent, and
+/// in reverse order after the response is received, so each middleware wraps all of the middleware registered after
+/// it.
+pub trait Middleware: Send + Sync {
+    /// Inspect or modify a request before it is sent.
+    ///
+    /// Returning a response short-circuits the request: it is not sent, and later middleware never sees it. The
+    /// response is still passed to the `after` hook of this and any earlier middleware.
+    fn before(&self, request: &mut Request) -> Result<Option<Response>, Error> {
+        let _ = request;
+        Ok(None)
+    }
+
+    /// Inspect or modify a response after it has been received.
+    ///
+    /// This is called once the response header has been received; the body may still be streaming. Returning an error
+    /// fails the request with it.
+    fn after(&self, response: &mut Response) -> Result<(), Error> {
+        let _ = response;
+        Ok(())
+    }
+}
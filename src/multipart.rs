@@ -0,0 +1,231 @@
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use util;
+
+
+/// A `multipart/form-data` request body made up of named parts.
+///
+/// Parts are streamed as the request is sent, so files are never read into memory as a whole. When used as a request
+/// body, the `Content-Type` header is set to include the boundary between parts, and the `Content-Length` header is set
+/// if the size of every part is known.
+///
+/// ```rust
+/// use chttp::multipart::{Form, Part};
+///
+/// let form = Form::new()
+///     .text("title", "Holiday")
+///     .part("photo", Part::bytes(vec![0xff, 0xd8, 0xff]).with_filename("photo.jpg").with_content_type("image/jpeg"));
+/// ```
+pub struct Form {
+    boundary: String,
+    /// The encoded form, as a sequence of chunks that are read one after the other.
+    chunks: Vec<Chunk>,
+    /// Index of the chunk currently being read.
+    current: usize,
+    /// The delimiter that ends the form, read after all chunks.
+    closing: Cursor<Vec<u8>>,
+}
+
+impl Form {
+    /// Create a new empty form with a random boundary.
+    pub fn new() -> Form {
+        let boundary = format!("------------------------{:016x}{:016x}", util::random(), util::random());
+        let closing = format!("--{}--\r\n", boundary).into_bytes();
+
+        Form {
+            boundary: boundary,
+            chunks: Vec::new(),
+            current: 0,
+            closing: Cursor::new(closing),
+        }
+    }
+
+    /// Add a text field.
+    pub fn text<N: Into<String>, V: Into<String>>(self, name: N, value: V) -> Form {
+        self.part(name, Part::bytes(value.into().into_bytes()))
+    }
+
+    /// Add a file field containing the file at the given path.
+    ///
+    /// The file name is taken from the path, and the content type is `application/octet-stream`. Use `Form::part` with
+    /// `Part::file` to change them.
+    pub fn file<N: Into<String>, P: AsRef<Path>>(self, name: N, path: P) -> io::Result<Form> {
+        Ok(self.part(name, Part::file(path)?))
+    }
+
+    /// Add a part with the given field name.
+    pub fn part<N: Into<String>>(mut self, name: N, part: Part) -> Form {
+        let mut header = format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"", self.boundary, escape(&name.into()));
+        if let Some(filename) = part.filename {
+            header.push_str(&format!("; filename=\"{}\"", escape(&filename)));
+        }
+        header.push_str("\r\n");
+        if let Some(content_type) = part.content_type {
+            header.push_str(&format!("Content-Type: {}\r\n", content_type));
+        }
+        header.push_str("\r\n");
+
+        self.chunks.push(Chunk::Bytes(Cursor::new(header.into_bytes())));
+        self.chunks.push(part.data);
+        self.chunks.push(Chunk::Bytes(Cursor::new(b"\r\n".to_vec())));
+        self
+    }
+
+    /// Get the boundary that separates the parts of the form.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Get the media type of the form, including the boundary, to send as the `Content-Type` header.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Get the size of the encoded form in bytes, if the size of every part is known.
+    pub fn len(&self) -> Option<u64> {
+        self.chunks.iter().fold(Some(self.closing.get_ref().len() as u64), |total, chunk| {
+            total.and_then(|total| chunk.len().map(|len| total + len))
+        })
+    }
+
+    /// Check if the form can be rewound, which is not the case if it contains a part read from a reader.
+    pub(crate) fn is_rewindable(&self) -> bool {
+        self.chunks.iter().all(|chunk| match chunk {
+            &Chunk::Reader(_) => false,
+            _ => true,
+        })
+    }
+
+    /// Rewind the form so that it can be sent again. Fails if the form contains a part read from a reader.
+    pub(crate) fn rewind(&mut self) -> io::Result<()> {
+        for chunk in self.chunks.iter_mut() {
+            chunk.rewind()?;
+        }
+
+        self.current = 0;
+        self.closing.set_position(0);
+
+        Ok(())
+    }
+}
+
+impl Default for Form {
+    fn default() -> Form {
+        Form::new()
+    }
+}
+
+impl Read for Form {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current < self.chunks.len() {
+            match self.chunks[self.current].read(buf)? {
+                0 => self.current += 1,
+                len => return Ok(len),
+            }
+        }
+
+        self.closing.read(buf)
+    }
+}
+
+
+/// The contents of a single part of a form.
+pub struct Part {
+    data: Chunk,
+    filename: Option<String>,
+    content_type: Option<String>,
+}
+
+impl Part {
+    /// Create a part from bytes in memory.
+    pub fn bytes<B: Into<Vec<u8>>>(bytes: B) -> Part {
+        Part {
+            data: Chunk::Bytes(Cursor::new(bytes.into())),
+            filename: None,
+            content_type: None,
+        }
+    }
+
+    /// Create a part that is streamed from a reader. The size of a reader is not known, so a form containing one is
+    /// sent without a `Content-Length`, and cannot be sent again when following a redirect or retrying.
+    pub fn reader<R: Read + Send + 'static>(reader: R) -> Part {
+        Part {
+            data: Chunk::Reader(Box::new(reader)),
+            filename: None,
+            content_type: None,
+        }
+    }
+
+    /// Create a part that is streamed from the file at the given path.
+    ///
+    /// The file name is taken from the path, and the content type is set to `application/octet-stream`.
+    pub fn file<P: AsRef<Path>>(path: P) -> io::Result<Part> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        Ok(Part {
+            data: Chunk::File(file, len),
+            filename: path.file_name().map(|name| name.to_string_lossy().into_owned()),
+            content_type: Some(String::from("application/octet-stream")),
+        })
+    }
+
+    /// Set the file name of the part.
+    pub fn with_filename<S: Into<String>>(mut self, filename: S) -> Part {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    /// Set the content type of the part.
+    pub fn with_content_type<S: Into<String>>(mut self, content_type: S) -> Part {
+        self.content_type = Some(content_type.into());
+        self
+    }
+}
+
+
+/// A piece of an encoded form.
+enum Chunk {
+    Bytes(Cursor<Vec<u8>>),
+    File(File, u64),
+    Reader(Box<Read + Send>),
+}
+
+impl Chunk {
+    fn len(&self) -> Option<u64> {
+        match self {
+            &Chunk::Bytes(ref bytes) => Some(bytes.get_ref().len() as u64),
+            &Chunk::File(_, len) => Some(len),
+            &Chunk::Reader(_) => None,
+        }
+    }
+
+    fn rewind(&mut self) -> io::Result<()> {
+        match self {
+            &mut Chunk::Bytes(ref mut bytes) => bytes.set_position(0),
+            &mut Chunk::File(ref mut file, _) => {
+                file.seek(SeekFrom::Start(0))?;
+            }
+            &mut Chunk::Reader(_) => return Err(io::ErrorKind::InvalidInput.into()),
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for Chunk {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            &mut Chunk::Bytes(ref mut bytes) => bytes.read(buf),
+            &mut Chunk::File(ref mut file, _) => file.read(buf),
+            &mut Chunk::Reader(ref mut reader) => reader.read(buf),
+        }
+    }
+}
+
+/// Escape a field name or file name for use in a quoted header parameter, the way browsers do.
+fn escape(value: &str) -> String {
+    value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
@@ -1,47 +1,188 @@
+use auth::Authentication;
+use curl;
+use error::{Error, ErrorKind};
 use http::{self, Uri};
+use retry::RetryPolicy;
+use ssl::{ClientCertificate, SslVersion};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
 
 /// Defines various protocol and connection options.
-#[derive(Clone, Debug)]
+///
+/// Options are set using the builder-style `with_*` methods, which allows new options to be added in the future without
+/// breaking existing code:
+///
+/// ```rust
+/// use chttp::{Options, RedirectPolicy};
+/// use std::time::Duration;
+///
+/// let options = Options::default()
+///     .with_timeout(Some(Duration::from_secs(60)))
+///     .with_redirect_policy(RedirectPolicy::Limit(10));
+/// ```
+///
+/// Options can also be attached to an individual request as an extension, in which case any options that have been set
+/// override the client's options for that request only:
+///
+/// ```rust
+/// use chttp::{http, Body, Options};
+/// use std::time::Duration;
+///
+/// let request = http::Request::get("https://example.org")
+///     .extension(Options::default().with_timeout(Some(Duration::from_secs(5))))
+///     .body(Body::Empty)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
 pub struct Options {
-    /// The policy for automatically following server redirects.
+    // Every option is wrapped in an `Option` that is `None` when the option has not been set, so that request options
+    // can be merged with client options.
+    redirect_policy: Option<RedirectPolicy>,
+    preferred_http_version: Option<Option<http::Version>>,
+    timeout: Option<Option<Duration>>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Option<Duration>>,
+    first_byte_timeout: Option<Option<Duration>>,
+    tcp_keepalive: Option<Option<Duration>>,
+    tcp_nodelay: Option<bool>,
+    connection_idle_timeout: Option<Option<Duration>>,
+    auto_referer: Option<bool>,
+    allow_redirect_downgrade: Option<bool>,
+    proxy: Option<Option<Uri>>,
+    ssl_cipher_list: Option<Option<String>>,
+    progress: Option<Option<ProgressCallback>>,
+    automatic_decompression: Option<bool>,
+    chunked_uploads: Option<bool>,
+    ssl_ca_certificate: Option<Option<PathBuf>>,
+    ssl_ca_path: Option<Option<PathBuf>>,
+    ssl_client_certificate: Option<Option<ClientCertificate>>,
+    ssl_min_version: Option<Option<SslVersion>>,
+    ssl_pinned_public_key: Option<Option<String>>,
+    danger_accept_invalid_certs: Option<bool>,
+    authentication: Option<Option<Authentication>>,
+    retry_policy: Option<Option<RetryPolicy>>,
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options::default()
+    }
+
+    /// Set the policy for automatically following server redirects.
     ///
     /// The default is to not follow redirects.
-    pub redirect_policy: RedirectPolicy,
+    pub fn with_redirect_policy(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect_policy = Some(policy);
+        self
+    }
 
-    /// A preferred HTTP version the client should attempt to use to communicate to the server with.
+    /// Set a preferred HTTP version the client should attempt to use to communicate to the server with.
     ///
     /// This is treated as a suggestion. A different version may be used if the server does not support it or negotiates
     /// a different version.
     ///
     /// The default value is `None` (any version).
-    pub preferred_http_version: Option<http::Version>,
+    pub fn with_preferred_http_version(mut self, version: Option<http::Version>) -> Self {
+        self.preferred_http_version = Some(version);
+        self
+    }
 
-    /// A timeout for the maximum time allowed for a request-response cycle.
+    /// Set a timeout for the maximum time allowed for a request-response cycle.
+    ///
+    /// When this timeout is reached, the request fails with `ErrorKind::Timeout`. This also caps the time allowed for
+    /// reading the response body, so use `with_read_timeout` instead to protect long streaming downloads.
     ///
     /// The default value is `None` (unlimited).
-    pub timeout: Option<Duration>,
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
 
-    /// A timeout for the initial connection phase.
+    /// Set a timeout for the initial connection phase, including resolving the host name and the TLS handshake.
+    ///
+    /// When this timeout is reached, the request fails with `ErrorKind::ConnectTimeout`.
     ///
     /// The default value is 300 seconds.
-    pub connect_timeout: Duration,
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set a timeout for how long a transfer may go without sending or receiving any data once connected, such as when
+    /// a server stops sending the response body. Time spent waiting for the response body to be read does not count.
+    /// Curl rounds the timeout up to whole seconds, and averages the transfer speed over the last few seconds, so a
+    /// transfer that stops may only fail up to five seconds after the timeout.
+    ///
+    /// Unlike the overall timeout, this does not limit how long a download that keeps making progress may take. When
+    /// this timeout is reached, the request fails with `ErrorKind::ReadTimeout`, or with `ErrorKind::FirstByteTimeout`
+    /// if the server has not responded yet.
+    ///
+    /// The default value is `None` (unlimited).
+    pub fn with_read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Set a timeout for receiving the first byte of the response, measured from the start of the request.
+    ///
+    /// When this timeout is reached, the request fails with `ErrorKind::FirstByteTimeout`.
+    ///
+    /// The default value is `None` (unlimited).
+    pub fn with_first_byte_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.first_byte_timeout = Some(timeout);
+        self
+    }
 
     /// Enable or disable TCP keepalive with a given probe interval.
     ///
     /// The default value is `None` (disabled).
-    pub tcp_keepalive: Option<Duration>,
+    pub fn with_tcp_keepalive(mut self, interval: Option<Duration>) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
 
     /// Enable or disable the `TCP_NODELAY` option.
     ///
     /// The default value is `false`.
-    pub tcp_nodelay: bool,
+    pub fn with_tcp_nodelay(mut self, enable: bool) -> Self {
+        self.tcp_nodelay = Some(enable);
+        self
+    }
+
+    /// Set how long an idle connection may sit in the connection pool before it is closed instead of being re-used.
+    ///
+    /// The default value is `None`, which uses the libcurl default of 118 seconds.
+    pub fn with_connection_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connection_idle_timeout = Some(timeout);
+        self
+    }
 
     /// Indicates whether the `Referer` header should be automatically updated.
-    pub auto_referer: bool,
+    ///
+    /// If enabled, each request made when following a redirect is sent with the URI that redirected to it as the
+    /// referer, except when redirecting from HTTPS to HTTP.
+    ///
+    /// The default value is `false`.
+    pub fn with_auto_referer(mut self, enable: bool) -> Self {
+        self.auto_referer = Some(enable);
+        self
+    }
+
+    /// Allow following redirects from HTTPS to plain HTTP.
+    ///
+    /// Such redirects would send the request over an insecure connection, so by default they are not followed and the
+    /// redirect response is returned instead.
+    ///
+    /// The default value is `false`.
+    pub fn with_allow_redirect_downgrade(mut self, allow: bool) -> Self {
+        self.allow_redirect_downgrade = Some(allow);
+        self
+    }
 
-    /// A proxy to use for requests.
+    /// Set a proxy to use for requests.
     ///
     /// The proxy protocol is specified by the URI scheme.
     ///
@@ -51,7 +192,10 @@ pub struct Options {
     /// - **`socks4a`**: SOCKS4a Proxy. Proxy resolves URL hostname.
     /// - **`socks5`**: SOCKS5 Proxy.
     /// - **`socks5h`**: SOCKS5 Proxy. Proxy resolves URL hostname.
-    pub proxy: Option<Uri>,
+    pub fn with_proxy(mut self, proxy: Option<Uri>) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
 
     /// Specify ciphers to use for TLS.
     ///
@@ -71,34 +215,406 @@ pub struct Options {
     ///
     /// By default this option is not set and corresponds to CURLOPT_SSL_CIPHER_LIST.
     ///
-    pub ssl_cipher_list: Option<String>,
-}
+    pub fn with_ssl_cipher_list(mut self, ciphers: Option<String>) -> Self {
+        self.ssl_cipher_list = Some(ciphers);
+        self
+    }
+
+    /// Set a file containing one or more PEM-encoded certificate authority certificates to verify servers with, instead
+    /// of the system default.
+    ///
+    /// The default value is `None` (use the default CA bundle).
+    pub fn with_ssl_ca_certificate(mut self, path: Option<PathBuf>) -> Self {
+        self.ssl_ca_certificate = Some(path);
+        self
+    }
+
+    /// Set a directory containing certificate authority certificates to verify servers with, instead of the system
+    /// default. The certificates must be named by their subject hash, as done by OpenSSL's `c_rehash` utility.
+    ///
+    /// The default value is `None` (use the default CA directory).
+    pub fn with_ssl_ca_path(mut self, path: Option<PathBuf>) -> Self {
+        self.ssl_ca_path = Some(path);
+        self
+    }
+
+    /// Set a client certificate to present to servers that request one.
+    ///
+    /// The default value is `None` (no client certificate).
+    pub fn with_ssl_client_certificate(mut self, certificate: Option<ClientCertificate>) -> Self {
+        self.ssl_client_certificate = Some(certificate);
+        self
+    }
 
-impl Default for Options {
-    fn default() -> Options {
+    /// Set the minimum TLS version to accept when connecting to servers.
+    ///
+    /// The default value is `None`, which uses the minimum version considered safe by libcurl and the TLS backend.
+    pub fn with_ssl_min_version(mut self, version: Option<SslVersion>) -> Self {
+        self.ssl_min_version = Some(version);
+        self
+    }
+
+    /// Pin the public key of servers. Connections to servers that do not present a matching public key fail with
+    /// `ErrorKind::BadServerCertificate`, even if their certificate is otherwise valid.
+    ///
+    /// The pin is either the path to a PEM or DER file containing the public key, or one or more base64-encoded
+    /// SHA-256 hashes of the public key, each prefixed with `sha256//` and separated by `;`.
+    ///
+    /// The default value is `None` (no pinning).
+    pub fn with_ssl_pinned_public_key(mut self, pin: Option<String>) -> Self {
+        self.ssl_pinned_public_key = Some(pin);
+        self
+    }
+
+    /// **Dangerous!** Disable verification of server certificates and host names.
+    ///
+    /// With verification disabled, any server can impersonate any other server, and all of the protection offered by
+    /// TLS is lost. Only use this to connect to local test servers with self-signed certificates, and never in
+    /// production. Prefer adding the test certificate authority with `with_ssl_ca_certificate` instead where possible.
+    ///
+    /// The default value is `false`.
+    pub fn with_danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.danger_accept_invalid_certs = Some(accept);
+        self
+    }
+
+    /// Set credentials to authenticate requests with.
+    ///
+    /// The default value is `None` (no authentication).
+    pub fn with_authentication(mut self, authentication: Option<Authentication>) -> Self {
+        self.authentication = Some(authentication);
+        self
+    }
+
+    /// Set a policy for automatically retrying requests that fail because of a transient problem.
+    ///
+    /// The default value is `None` (never retry).
+    pub fn with_retry_policy(mut self, policy: Option<RetryPolicy>) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Set a callback to be invoked periodically with the progress of the request upload and response download.
+    ///
+    /// Returning `false` from the callback aborts the request with `ErrorKind::Aborted`. The callback is invoked on the
+    /// client's background thread, so it should return quickly.
+    ///
+    /// The default value is `None` (do not report progress).
+    pub fn with_progress(mut self, callback: Option<ProgressCallback>) -> Self {
+        self.progress = Some(callback);
+        self
+    }
+
+    /// Enable or disable automatic decompression of response bodies.
+    ///
+    /// When enabled, an `Accept-Encoding` header listing all encodings supported by libcurl is sent, and compressed
+    /// response bodies are decoded transparently. Since the body is no longer encoded, the `Content-Encoding` and
+    /// `Content-Length` headers are removed from decoded responses. Disable this to receive the raw bytes sent by the
+    /// server.
+    ///
+    /// The default value is `true`.
+    pub fn with_automatic_decompression(mut self, enable: bool) -> Self {
+        self.automatic_decompression = Some(enable);
+        self
+    }
+
+    /// Enable or disable chunked transfer encoding for request bodies of unknown size, such as those read from a stream.
+    ///
+    /// If disabled, such bodies are read into memory before the request is sent, so that they can be sent with a
+    /// `Content-Length` header. This is needed for servers that do not accept chunked requests. Bodies of known size are
+    /// always sent with a `Content-Length`.
+    ///
+    /// The body is read in full on the thread sending the request, even when using `send_async`, and without any limit
+    /// on its size. Only disable this for streams that are quick to read and known to be reasonably small.
+    ///
+    /// The default value is `true`.
+    pub fn with_chunked_uploads(mut self, enable: bool) -> Self {
+        self.chunked_uploads = Some(enable);
+        self
+    }
+
+    /// Merge these options on top of another set of options. Options set here take precedence.
+    pub(crate) fn merge(&self, defaults: &Options) -> Options {
         Options {
-            redirect_policy: RedirectPolicy::default(),
-            preferred_http_version: None,
-            timeout: None,
-            connect_timeout: Duration::from_secs(300),
-            tcp_keepalive: None,
-            tcp_nodelay: false,
-            auto_referer: false,
-            proxy: None,
-            ssl_cipher_list: None,
+            redirect_policy: self.redirect_policy.clone().or_else(|| defaults.redirect_policy.clone()),
+            preferred_http_version: self.preferred_http_version.or(defaults.preferred_http_version),
+            timeout: self.timeout.or(defaults.timeout),
+            connect_timeout: self.connect_timeout.or(defaults.connect_timeout),
+            read_timeout: self.read_timeout.or(defaults.read_timeout),
+            first_byte_timeout: self.first_byte_timeout.or(defaults.first_byte_timeout),
+            tcp_keepalive: self.tcp_keepalive.or(defaults.tcp_keepalive),
+            tcp_nodelay: self.tcp_nodelay.or(defaults.tcp_nodelay),
+            connection_idle_timeout: self.connection_idle_timeout.or(defaults.connection_idle_timeout),
+            auto_referer: self.auto_referer.or(defaults.auto_referer),
+            allow_redirect_downgrade: self.allow_redirect_downgrade.or(defaults.allow_redirect_downgrade),
+            proxy: self.proxy.clone().or_else(|| defaults.proxy.clone()),
+            ssl_cipher_list: self.ssl_cipher_list.clone().or_else(|| defaults.ssl_cipher_list.clone()),
+            progress: self.progress.clone().or_else(|| defaults.progress.clone()),
+            automatic_decompression: self.automatic_decompression.or(defaults.automatic_decompression),
+            chunked_uploads: self.chunked_uploads.or(defaults.chunked_uploads),
+            ssl_ca_certificate: self.ssl_ca_certificate.clone().or_else(|| defaults.ssl_ca_certificate.clone()),
+            ssl_ca_path: self.ssl_ca_path.clone().or_else(|| defaults.ssl_ca_path.clone()),
+            ssl_client_certificate: self.ssl_client_certificate.clone().or_else(|| defaults.ssl_client_certificate.clone()),
+            ssl_min_version: self.ssl_min_version.or(defaults.ssl_min_version),
+            ssl_pinned_public_key: self.ssl_pinned_public_key.clone().or_else(|| defaults.ssl_pinned_public_key.clone()),
+            danger_accept_invalid_certs: self.danger_accept_invalid_certs.or(defaults.danger_accept_invalid_certs),
+            authentication: self.authentication.clone().or_else(|| defaults.authentication.clone()),
+            retry_policy: self.retry_policy.or(defaults.retry_policy),
+        }
+    }
+
+    pub(crate) fn redirect_policy(&self) -> RedirectPolicy {
+        self.redirect_policy.clone().unwrap_or_default()
+    }
+
+    pub(crate) fn preferred_http_version(&self) -> Option<http::Version> {
+        self.preferred_http_version.unwrap_or(None)
+    }
+
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout.unwrap_or(None)
+    }
+
+    pub(crate) fn connect_timeout(&self) -> Duration {
+        self.connect_timeout.unwrap_or(Duration::from_secs(300))
+    }
+
+    pub(crate) fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout.unwrap_or(None)
+    }
+
+    pub(crate) fn first_byte_timeout(&self) -> Option<Duration> {
+        self.first_byte_timeout.unwrap_or(None)
+    }
+
+    pub(crate) fn tcp_keepalive(&self) -> Option<Duration> {
+        self.tcp_keepalive.unwrap_or(None)
+    }
+
+    pub(crate) fn tcp_nodelay(&self) -> bool {
+        self.tcp_nodelay.unwrap_or(false)
+    }
+
+    pub(crate) fn connection_idle_timeout(&self) -> Option<Duration> {
+        self.connection_idle_timeout.unwrap_or(None)
+    }
+
+    pub(crate) fn auto_referer(&self) -> bool {
+        self.auto_referer.unwrap_or(false)
+    }
+
+    pub(crate) fn allow_redirect_downgrade(&self) -> bool {
+        self.allow_redirect_downgrade.unwrap_or(false)
+    }
+
+    pub(crate) fn proxy(&self) -> Option<&Uri> {
+        self.proxy.as_ref().and_then(Option::as_ref)
+    }
+
+    pub(crate) fn ssl_cipher_list(&self) -> Option<&str> {
+        self.ssl_cipher_list.as_ref().and_then(Option::as_ref).map(String::as_str)
+    }
+
+    pub(crate) fn progress(&self) -> Option<&ProgressCallback> {
+        self.progress.as_ref().and_then(Option::as_ref)
+    }
+
+    pub(crate) fn automatic_decompression(&self) -> bool {
+        self.automatic_decompression.unwrap_or(true)
+    }
+
+    pub(crate) fn chunked_uploads(&self) -> bool {
+        self.chunked_uploads.unwrap_or(true)
+    }
+
+    pub(crate) fn ssl_ca_certificate(&self) -> Option<&PathBuf> {
+        self.ssl_ca_certificate.as_ref().and_then(Option::as_ref)
+    }
+
+    pub(crate) fn ssl_ca_path(&self) -> Option<&PathBuf> {
+        self.ssl_ca_path.as_ref().and_then(Option::as_ref)
+    }
+
+    pub(crate) fn ssl_client_certificate(&self) -> Option<&ClientCertificate> {
+        self.ssl_client_certificate.as_ref().and_then(Option::as_ref)
+    }
+
+    pub(crate) fn ssl_min_version(&self) -> Option<SslVersion> {
+        self.ssl_min_version.unwrap_or(None)
+    }
+
+    pub(crate) fn ssl_pinned_public_key(&self) -> Option<&str> {
+        self.ssl_pinned_public_key.as_ref().and_then(Option::as_ref).map(String::as_str)
+    }
+
+    pub(crate) fn danger_accept_invalid_certs(&self) -> bool {
+        self.danger_accept_invalid_certs.unwrap_or(false)
+    }
+
+    pub(crate) fn authentication(&self) -> Option<&Authentication> {
+        self.authentication.as_ref().and_then(Option::as_ref)
+    }
+
+    pub(crate) fn retry_policy(&self) -> Option<RetryPolicy> {
+        self.retry_policy.unwrap_or(None)
+    }
+
+    /// Check that the options are valid and supported by the version of libcurl in use.
+    ///
+    /// This catches mistakes that curl would otherwise only report once a request is already underway.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if let Some(proxy) = self.proxy() {
+            match proxy.scheme_part().map(|scheme| scheme.as_str()) {
+                None | Some("http") | Some("socks4") | Some("socks4a") | Some("socks5") | Some("socks5h") => {}
+                Some("https") => {
+                    if !curl::Version::get().feature_ssl() {
+                        return Err(ErrorKind::InvalidConfiguration(String::from("HTTPS proxies require libcurl to be built with TLS support")).into());
+                    }
+                }
+                Some(scheme) => {
+                    return Err(ErrorKind::InvalidConfiguration(format!("unsupported proxy scheme: {}", scheme)).into());
+                }
+            }
+
+            if proxy.host().is_none() {
+                return Err(ErrorKind::InvalidConfiguration(format!("proxy URI has no host: {}", proxy)).into());
+            }
+        }
+
+        if let Some(ciphers) = self.ssl_cipher_list() {
+            if !is_valid_cipher_list(ciphers) {
+                return Err(ErrorKind::InvalidConfiguration(format!("malformed SSL cipher list: {:?}", ciphers)).into());
+            }
+        }
+
+        if let Some(path) = self.ssl_ca_certificate() {
+            if !path.is_file() {
+                return Err(ErrorKind::InvalidConfiguration(format!("CA certificate file not found: {}", path.display())).into());
+            }
         }
+
+        if let Some(path) = self.ssl_ca_path() {
+            if !path.is_dir() {
+                return Err(ErrorKind::InvalidConfiguration(format!("CA certificate directory not found: {}", path.display())).into());
+            }
+        }
+
+        if let Some(certificate) = self.ssl_client_certificate() {
+            for path in certificate.paths() {
+                if !path.is_file() {
+                    return Err(ErrorKind::InvalidConfiguration(format!("client certificate file not found: {}", path.display())).into());
+                }
+            }
+        }
+
+        if let Some(authentication) = self.authentication() {
+            authentication.validate()?;
+        }
+
+        if let Some(pin) = self.ssl_pinned_public_key() {
+            if !is_valid_public_key_pin(pin) {
+                return Err(ErrorKind::InvalidConfiguration(format!("invalid public key pin: {:?}", pin)).into());
+            }
+        }
+
+        Ok(())
     }
 }
 
-impl Options {
-    pub fn new() -> Options {
-        Options::default()
+/// Check that a public key pin is either a list of hashes or the path to an existing key file.
+fn is_valid_public_key_pin(pin: &str) -> bool {
+    if pin.starts_with("sha256//") {
+        pin.split(';').all(|hash| {
+            hash.starts_with("sha256//") && hash.len() > 8 && hash[8..].chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/' || c == '=')
+        })
+    } else {
+        Path::new(pin).is_file()
+    }
+}
+
+/// Check that a cipher list is syntactically valid. Whether the named ciphers exist depends on the TLS backend, and can
+/// only be checked by curl when connecting.
+fn is_valid_cipher_list(ciphers: &str) -> bool {
+    let mut names = ciphers
+        .split(|c| c == ':' || c == ',' || c == ' ')
+        .filter(|name| !name.is_empty())
+        .peekable();
+
+    // There must be at least one cipher string.
+    if names.peek().is_none() {
+        return false;
+    }
+
+    names.all(|name| {
+        // Operators may only appear as a prefix.
+        let name = name.trim_left_matches(|c| c == '!' || c == '-' || c == '+');
+
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '+' || c == '.' || c == '=' || c == '@')
+    })
+}
+
+
+/// A snapshot of the progress of a request.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Progress {
+    pub(crate) upload_total: Option<u64>,
+    pub(crate) uploaded: u64,
+    pub(crate) download_total: Option<u64>,
+    pub(crate) downloaded: u64,
+}
+
+impl Progress {
+    /// Get the size of the request body in bytes, if known.
+    pub fn upload_total(&self) -> Option<u64> {
+        self.upload_total
+    }
+
+    /// Get the number of bytes of the request body sent so far.
+    pub fn uploaded(&self) -> u64 {
+        self.uploaded
+    }
+
+    /// Get the size of the response body in bytes, if known.
+    pub fn download_total(&self) -> Option<u64> {
+        self.download_total
+    }
+
+    /// Get the number of bytes of the response body received so far.
+    pub fn downloaded(&self) -> u64 {
+        self.downloaded
+    }
+}
+
+/// A shareable callback for reporting the progress of requests.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<Fn(&Progress) -> bool + Send + Sync>);
+
+impl ProgressCallback {
+    /// Create a progress callback from a function. Returning `false` from the function aborts the request.
+    pub fn new<F>(callback: F) -> ProgressCallback
+        where F: Fn(&Progress) -> bool + Send + Sync + 'static
+    {
+        ProgressCallback(Arc::new(callback))
+    }
+
+    pub(crate) fn call(&self, progress: &Progress) -> bool {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ProgressCallback")
     }
 }
 
 
 /// Describes a policy for handling server redirects.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+///
+/// Whichever policy is used, redirects from HTTPS to HTTP are only followed if allowed with
+/// `Options::with_allow_redirect_downgrade`, and redirects to other protocols are never followed. When a redirect leads
+/// to a different origin, the `Authorization` and `Cookie` headers and any configured credentials are not sent there.
+#[derive(Clone)]
 pub enum RedirectPolicy {
     /// Do not apply any special treatment to redirect responses. The response will be return as-is and redirects will
     /// not be followed.
@@ -109,6 +625,18 @@ pub enum RedirectPolicy {
     Follow,
     /// Follow redirects automatically up to a maximum number of redirects.
     Limit(u32),
+    /// Call a function to decide whether to follow each redirect. If the function returns `false`, the redirect
+    /// response is returned as-is.
+    Custom(Arc<Fn(&Redirect) -> bool + Send + Sync>),
+}
+
+impl RedirectPolicy {
+    /// Create a policy that calls the given function to decide whether to follow each redirect.
+    pub fn custom<F>(callback: F) -> RedirectPolicy
+        where F: Fn(&Redirect) -> bool + Send + Sync + 'static
+    {
+        RedirectPolicy::Custom(Arc::new(callback))
+    }
 }
 
 impl Default for RedirectPolicy {
@@ -116,3 +644,54 @@ impl Default for RedirectPolicy {
         RedirectPolicy::None
     }
 }
+
+impl PartialEq for RedirectPolicy {
+    fn eq(&self, other: &RedirectPolicy) -> bool {
+        match (self, other) {
+            (&RedirectPolicy::None, &RedirectPolicy::None) => true,
+            (&RedirectPolicy::Follow, &RedirectPolicy::Follow) => true,
+            (&RedirectPolicy::Limit(a), &RedirectPolicy::Limit(b)) => a == b,
+            (&RedirectPolicy::Custom(ref a), &RedirectPolicy::Custom(ref b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for RedirectPolicy {}
+
+impl fmt::Debug for RedirectPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &RedirectPolicy::None => f.write_str("None"),
+            &RedirectPolicy::Follow => f.write_str("Follow"),
+            &RedirectPolicy::Limit(max) => write!(f, "Limit({})", max),
+            &RedirectPolicy::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+
+/// A redirect that is about to be followed, given to custom redirect policies.
+#[derive(Clone, Debug)]
+pub struct Redirect {
+    pub(crate) status: http::StatusCode,
+    pub(crate) from: Uri,
+    pub(crate) to: Uri,
+}
+
+impl Redirect {
+    /// Get the status code of the redirect response.
+    pub fn status(&self) -> http::StatusCode {
+        self.status
+    }
+
+    /// Get the URI of the request that was redirected.
+    pub fn from(&self) -> &Uri {
+        &self.from
+    }
+
+    /// Get the URI being redirected to.
+    pub fn to(&self) -> &Uri {
+        &self.to
+    }
+}
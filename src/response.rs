@@ -0,0 +1,267 @@
+use encoding_rs::{Encoding, UTF_8};
+use error::{Error, ErrorKind, HttpStatusError};
+use http::{self, Uri};
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+
+/// Provides extension methods for accessing information about how a response was received.
+pub trait ResponseExt {
+    /// Get the final URI of the request, after following any redirects.
+    fn effective_uri(&self) -> Option<&Uri>;
+
+    /// Get the redirect responses that were followed before receiving this response, in the order they were received.
+    ///
+    /// Each redirect response has its own effective URI, which is the URI that returned the redirect.
+    fn redirects(&self) -> &[http::Response<()>];
+
+    /// Get the IP address and port of the server that sent the response.
+    fn remote_addr(&self) -> Option<SocketAddr>;
+
+    /// Get timing information about the transfer that produced this response.
+    fn metrics(&self) -> Option<&Metrics>;
+
+    /// Get the informational (`1xx`) responses, such as `100 Continue` or `103 Early Hints`, that the server sent
+    /// before this response, in the order they were received.
+    fn informational(&self) -> &[http::Response<()>];
+
+    /// Get the trailer fields that the server sent after the response body.
+    fn trailers(&self) -> Option<&Trailers>;
+}
+
+impl<T> ResponseExt for http::Response<T> {
+    fn effective_uri(&self) -> Option<&Uri> {
+        self.extensions().get::<EffectiveUri>().map(|uri| &uri.0)
+    }
+
+    fn redirects(&self) -> &[http::Response<()>] {
+        self.extensions().get::<Redirects>().map(|redirects| redirects.0.as_slice()).unwrap_or(&[])
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.extensions().get::<RemoteAddr>().map(|addr| addr.0)
+    }
+
+    fn metrics(&self) -> Option<&Metrics> {
+        self.extensions().get::<Metrics>()
+    }
+
+    fn informational(&self) -> &[http::Response<()>] {
+        self.extensions().get::<Informational>().map(|responses| responses.0.as_slice()).unwrap_or(&[])
+    }
+
+    fn trailers(&self) -> Option<&Trailers> {
+        self.extensions().get::<Trailers>()
+    }
+}
+
+
+/// Maximum number of bytes of the response body to keep in an error for an unsuccessful status.
+const MAX_ERROR_BODY_SIZE: u64 = 4096;
+
+
+/// Provides extension methods for reading the body of a response.
+///
+/// Unlike `Body::text`, which always expects UTF-8, these methods decode the body using the charset of the response. A
+/// byte order mark at the start of the body takes precedence, followed by the `charset` parameter of the
+/// `Content-Type` header. If neither is present, the body is decoded as UTF-8.
+pub trait ResponseBodyExt {
+    /// Read the response body as a string, decoded using the charset of the response.
+    ///
+    /// Fails with `ErrorKind::InvalidCharset` if the charset is not supported, or if the body is not valid text in it.
+    fn text(&mut self) -> Result<String, Error>;
+
+    /// Read the response body as a string, decoded using the charset of the response, replacing any invalid sequences
+    /// with the replacement character `U+FFFD`. An unsupported charset falls back to UTF-8.
+    fn text_lossy(&mut self) -> Result<String, Error>;
+
+    /// Turn a response with a client error (`4xx`) or server error (`5xx`) status into an `ErrorKind::HttpStatus`
+    /// error, and return any other response as-is.
+    ///
+    /// The error contains the status and headers of the response, and the start of the response body as text, which
+    /// usually explains what went wrong. At most 4 KiB of the body is read.
+    fn error_for_status(self) -> Result<Self, Error> where Self: Sized;
+}
+
+impl<T: Read> ResponseBodyExt for http::Response<T> {
+    fn text(&mut self) -> Result<String, Error> {
+        read_text(self, None, false)
+    }
+
+    fn text_lossy(&mut self) -> Result<String, Error> {
+        read_text(self, None, true)
+    }
+
+    fn error_for_status(mut self) -> Result<Self, Error> {
+        let status = self.status();
+        if !status.is_client_error() && !status.is_server_error() {
+            return Ok(self);
+        }
+
+        // The body only serves to explain the error, so failing to read it should not hide the status.
+        let body = read_text(&mut self, Some(MAX_ERROR_BODY_SIZE), true).unwrap_or_default();
+        let method = self.extensions().get::<EffectiveMethod>().map(|method| method.0.clone());
+        let uri = self.effective_uri().cloned();
+
+        let error = Error::from(ErrorKind::HttpStatus(Box::new(HttpStatusError::new(status, self.into_parts().0.headers, body))));
+
+        Err(match (method, uri) {
+            (Some(method), Some(uri)) => error.with_request(&method, &uri),
+            _ => error,
+        })
+    }
+}
+
+/// Read the response body as text, or at most the given number of bytes of it.
+fn read_text<T: Read>(response: &mut http::Response<T>, limit: Option<u64>, lossy: bool) -> Result<String, Error> {
+    let label = response.headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(charset)
+        .map(str::to_owned);
+
+    let mut bytes = Vec::new();
+    match limit {
+        Some(limit) => response.body_mut().by_ref().take(limit).read_to_end(&mut bytes)?,
+        None => response.body_mut().read_to_end(&mut bytes)?,
+    };
+
+    let encoding = match Encoding::for_bom(&bytes) {
+        Some((encoding, _)) => encoding,
+        None => match label {
+            Some(label) => match Encoding::for_label(label.as_bytes()) {
+                Some(encoding) => encoding,
+                None if lossy => UTF_8,
+                None => return Err(ErrorKind::InvalidCharset(format!("unsupported charset: {}", label)).into()),
+            },
+            None => UTF_8,
+        },
+    };
+
+    // Decoding strips the byte order mark, if there is one.
+    let (text, _, malformed) = encoding.decode(&bytes);
+
+    if malformed && !lossy {
+        return Err(if encoding == UTF_8 {
+            ErrorKind::InvalidUtf8
+        } else {
+            ErrorKind::InvalidCharset(format!("response body is not valid {}", encoding.name()))
+        }.into());
+    }
+
+    Ok(text.into_owned())
+}
+
+/// Get the value of the `charset` parameter of a media type, if present.
+fn charset(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).filter_map(|parameter| {
+        let mut parts = parameter.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("charset") => {
+                Some(value.trim().trim_matches('"'))
+            }
+            _ => None,
+        }
+    }).next()
+}
+
+
+/// Response extension holding the URI that the response was received from.
+pub(crate) struct EffectiveUri(pub Uri);
+
+/// Response extension holding the method of the request that the response was received for.
+pub(crate) struct EffectiveMethod(pub http::Method);
+
+/// Response extension holding the redirect responses followed before the final response.
+pub(crate) struct Redirects(pub Vec<http::Response<()>>);
+
+/// Response extension holding the address of the server that sent the response.
+pub(crate) struct RemoteAddr(pub SocketAddr);
+
+/// Response extension holding the informational responses received before the final response.
+pub(crate) struct Informational(pub Vec<http::Response<()>>);
+
+
+/// Trailer fields sent by the server after the response body.
+///
+/// Trailers can only be sent after a body of unknown size, such as a chunked HTTP/1.1 body, so they only become
+/// available once the response body has been read completely.
+#[derive(Clone, Debug, Default)]
+pub struct Trailers {
+    fields: Arc<Mutex<Option<http::HeaderMap>>>,
+}
+
+impl Trailers {
+    /// Get the trailer fields, or `None` if the response body has not been received completely yet. The fields are
+    /// empty if the server did not send any trailers.
+    pub fn get(&self) -> Option<http::HeaderMap> {
+        self.fields.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set(&self, fields: http::HeaderMap) {
+        *self.fields.lock().unwrap() = Some(fields);
+    }
+}
+
+
+/// Timing information about a transfer.
+///
+/// All times are measured from the start of the request, and include the time spent following any redirects. Since a
+/// response is returned as soon as its header has been received, the metrics are updated once more when the transfer is
+/// complete, which is when the total time becomes available.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    timings: Arc<Mutex<Timings>>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Timings {
+    pub name_lookup: Duration,
+    pub connect: Duration,
+    pub tls_handshake: Duration,
+    pub first_byte: Duration,
+    pub redirect: Duration,
+    pub total: Option<Duration>,
+}
+
+impl Metrics {
+    /// Time until the host name was resolved.
+    pub fn name_lookup_time(&self) -> Duration {
+        self.timings().name_lookup
+    }
+
+    /// Time until the connection to the server was established.
+    pub fn connect_time(&self) -> Duration {
+        self.timings().connect
+    }
+
+    /// Time until the TLS handshake with the server was completed. This is zero if TLS was not used.
+    pub fn tls_handshake_time(&self) -> Duration {
+        self.timings().tls_handshake
+    }
+
+    /// Time until the first byte of the response was received.
+    pub fn first_byte_time(&self) -> Duration {
+        self.timings().first_byte
+    }
+
+    /// Time spent following redirects before the final request was started.
+    pub fn redirect_time(&self) -> Duration {
+        self.timings().redirect
+    }
+
+    /// Total time taken by the transfer, or `None` if the response body has not been received completely yet.
+    pub fn total_time(&self) -> Option<Duration> {
+        self.timings().total
+    }
+
+    pub(crate) fn update(&self, timings: Timings) {
+        *self.timings.lock().unwrap() = timings;
+    }
+
+    fn timings(&self) -> Timings {
+        *self.timings.lock().unwrap()
+    }
+}
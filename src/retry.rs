@@ -0,0 +1,138 @@
+use http;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use util;
+
+
+/// A policy for automatically retrying requests that fail because of a transient problem.
+///
+/// A request is retried if connecting to the server fails, the server closes the connection without sending a
+/// response, the request times out, or the server responds with `429 Too Many Requests` or `503 Service Unavailable`.
+/// Requests are only retried if the response has not been returned yet, and if the request body can be sent again,
+/// which is not possible for bodies created from a reader.
+///
+/// Between attempts, the client waits for an exponentially increasing delay with random jitter, or for as long as the
+/// server asks with a `Retry-After` header, whichever is longer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    retry_non_idempotent: bool,
+}
+
+impl RetryPolicy {
+    /// Create a new retry policy that makes at most the given number of attempts, including the first one.
+    pub fn new(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: max_attempts,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(30),
+            retry_non_idempotent: false,
+        }
+    }
+
+    /// Set the delay before the first retry. Each later retry waits twice as long as the one before it.
+    ///
+    /// The default value is 100 milliseconds.
+    pub fn with_base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Set the maximum delay between attempts. This also caps delays requested by the server with `Retry-After`.
+    ///
+    /// The default value is 30 seconds.
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Also retry requests with non-idempotent methods, such as `POST` and `PATCH`.
+    ///
+    /// Retrying a non-idempotent request can cause its effects to happen more than once, if the server received the
+    /// request but the response got lost.
+    ///
+    /// The default value is `false`.
+    pub fn with_retry_non_idempotent(mut self, retry: bool) -> Self {
+        self.retry_non_idempotent = retry;
+        self
+    }
+
+    /// Check if a request with the given method may be retried after the given number of attempts.
+    pub(crate) fn allows(&self, method: &http::Method, attempts: u32) -> bool {
+        attempts < self.max_attempts && (self.retry_non_idempotent || is_idempotent(method))
+    }
+
+    /// Get the delay to wait before the next attempt, after the given number of attempts.
+    pub(crate) fn delay(&self, attempts: u32, retry_after: Option<Duration>) -> Duration {
+        // Double the delay for every attempt, taking care not to overflow.
+        let factor = 1u32.checked_shl(attempts.saturating_sub(1)).unwrap_or(u32::max_value());
+        let backoff = self.base_delay.checked_mul(factor).unwrap_or(self.max_delay).min(self.max_delay);
+
+        // Pick a random delay between half and all of the backoff, so that clients that failed at the same time do not
+        // all retry at the same time as well.
+        let half = backoff / 2;
+        let jitter = half.checked_mul((util::random() % 1000) as u32).map_or(half, |jitter| jitter / 1000);
+        let delay = half + jitter;
+
+        match retry_after {
+            Some(retry_after) => delay.max(retry_after).min(self.max_delay),
+            None => delay,
+        }
+    }
+}
+
+/// Check if a response status indicates that the request may succeed if retried later.
+pub(crate) fn is_retryable_status(status: http::StatusCode) -> bool {
+    status == http::StatusCode::TOO_MANY_REQUESTS || status == http::StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// Parse the value of a `Retry-After` header, which is either a number of seconds or an HTTP date.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // A date in the past means that the request can be retried right away.
+    parse_http_date(value).map(|date| date.duration_since(SystemTime::now()).unwrap_or(Duration::from_secs(0)))
+}
+
+fn is_idempotent(method: &http::Method) -> bool {
+    match *method {
+        http::Method::GET | http::Method::HEAD | http::Method::OPTIONS | http::Method::TRACE | http::Method::PUT | http::Method::DELETE => true,
+        _ => false,
+    }
+}
+
+/// Parse a date in the preferred HTTP date format, such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let parts = value.split(' ').collect::<Vec<_>>();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return None;
+    }
+
+    let day = parts[1].parse::<u64>().ok()?;
+    let month = match parts[2] {
+        "Jan" => 1, "Feb" => 2, "Mar" => 3, "Apr" => 4, "May" => 5, "Jun" => 6,
+        "Jul" => 7, "Aug" => 8, "Sep" => 9, "Oct" => 10, "Nov" => 11, "Dec" => 12,
+        _ => return None,
+    };
+    let year = parts[3].parse::<u64>().ok()?;
+
+    let time = parts[4].split(':').map(|part| part.parse::<u64>().ok()).collect::<Option<Vec<_>>>()?;
+    if time.len() != 3 || year < 1970 || day < 1 || day > 31 || time[0] > 23 || time[1] > 59 || time[2] > 60 {
+        return None;
+    }
+
+    // Count the days since the Unix epoch, treating March as the first month of the year so that leap days come last.
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let days_before_year = 365 * y + y / 4 - y / 100 + y / 400;
+    let days_before_month = (153 * m + 2) / 5;
+    let days = days_before_year + days_before_month + day - 1 - 719_468;
+
+    let seconds = days * 86_400 + time[0] * 3_600 + time[1] * 60 + time[2];
+
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
@@ -0,0 +1,170 @@
+use curl;
+use curl_sys;
+use std::os::raw::c_void;
+use std::sync::{Condvar, Mutex};
+use super::*;
+
+
+/// A curl share handle, used to share caches between multiple transfers.
+///
+/// Easy handles attached to the same share handle share whichever types of data have been enabled on it. Access to the
+/// shared data is serialized by locks owned by the share handle, so attached transfers may run on any thread.
+pub struct Share {
+    raw: *mut curl_sys::CURLSH,
+    /// Locks for each type of shared data. Boxed so that its address remains stable while curl holds a pointer to it.
+    locks: Box<Locks>,
+}
+
+impl Share {
+    /// Create a new share handle that does not share anything yet.
+    pub fn new() -> Result<Share, Error> {
+        let raw = unsafe {
+            curl_sys::curl_share_init()
+        };
+
+        if raw.is_null() {
+            return Err(ErrorKind::Curl(String::from("failed to create share handle")).into());
+        }
+
+        let share = Share {
+            raw: raw,
+            locks: Box::new(Locks::new()),
+        };
+
+        unsafe {
+            let lock_fn = lock as curl_sys::curl_lock_function;
+            let unlock_fn = unlock as curl_sys::curl_unlock_function;
+            let locks = &*share.locks as *const Locks as *mut c_void;
+
+            share.setopt(curl_sys::curl_share_setopt(raw, curl_sys::CURLSHOPT_LOCKFUNC, lock_fn))?;
+            share.setopt(curl_sys::curl_share_setopt(raw, curl_sys::CURLSHOPT_UNLOCKFUNC, unlock_fn))?;
+            share.setopt(curl_sys::curl_share_setopt(raw, curl_sys::CURLSHOPT_USERDATA, locks))?;
+        }
+
+        Ok(share)
+    }
+
+    /// Share DNS lookups between all attached transfers.
+    pub fn share_dns(&mut self) -> Result<(), Error> {
+        self.share(curl_sys::CURL_LOCK_DATA_DNS)
+    }
+
+    /// Share TLS session IDs between all attached transfers, so that new connections can resume an existing session
+    /// instead of performing a full handshake.
+    pub fn share_ssl_sessions(&mut self) -> Result<(), Error> {
+        self.share(curl_sys::CURL_LOCK_DATA_SSL_SESSION)
+    }
+
+    /// Share cookies between all attached transfers.
+    pub fn share_cookies(&mut self) -> Result<(), Error> {
+        self.share(curl_sys::CURL_LOCK_DATA_COOKIE)
+    }
+
+    /// Attach an easy handle to this share handle.
+    ///
+    /// The given handle must be a valid easy handle that does not outlive the share handle.
+    pub unsafe fn attach(&self, handle: *mut curl_sys::CURL) -> Result<(), Error> {
+        let code = curl_sys::curl_easy_setopt(handle, curl_sys::CURLOPT_SHARE, self.raw);
+
+        if code == curl_sys::CURLE_OK {
+            Ok(())
+        } else {
+            Err(curl::Error::new(code).into())
+        }
+    }
+
+    fn share(&mut self, data: curl_sys::curl_lock_data) -> Result<(), Error> {
+        unsafe {
+            self.setopt(curl_sys::curl_share_setopt(self.raw, curl_sys::CURLSHOPT_SHARE, data))
+        }
+    }
+
+    fn setopt(&self, code: curl_sys::CURLSHcode) -> Result<(), Error> {
+        if code == curl_sys::CURLSHE_OK {
+            Ok(())
+        } else {
+            Err(curl::ShareError::new(code).into())
+        }
+    }
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        unsafe {
+            curl_sys::curl_share_cleanup(self.raw);
+        }
+    }
+}
+
+// All access to the share handle's data by curl is guarded by the lock callbacks, so it is safe to use the share
+// handle from multiple threads.
+unsafe impl Send for Share {}
+unsafe impl Sync for Share {}
+
+
+/// A set of locks, one for each type of data curl might ask us to lock.
+struct Locks {
+    locks: Vec<Lock>,
+}
+
+impl Locks {
+    fn new() -> Locks {
+        Locks {
+            locks: (0..=curl_sys::CURL_LOCK_DATA_CONNECT).map(|_| Lock::new()).collect(),
+        }
+    }
+
+    fn get(&self, data: curl_sys::curl_lock_data) -> Option<&Lock> {
+        self.locks.get(data as usize)
+    }
+}
+
+/// A simple lock that, unlike a mutex guard, can be acquired and released from separate function calls.
+struct Lock {
+    locked: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Lock {
+    fn new() -> Lock {
+        Lock {
+            locked: Mutex::new(false),
+            condvar: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) {
+        let mut locked = self.locked.lock().unwrap();
+
+        while *locked {
+            locked = self.condvar.wait(locked).unwrap();
+        }
+
+        *locked = true;
+    }
+
+    fn release(&self) {
+        *self.locked.lock().unwrap() = false;
+        self.condvar.notify_one();
+    }
+}
+
+extern "C" fn lock(_: *mut curl_sys::CURL, data: curl_sys::curl_lock_data, _: curl_sys::curl_lock_access, userptr: *mut c_void) {
+    let locks = unsafe {
+        &*(userptr as *const Locks)
+    };
+
+    if let Some(lock) = locks.get(data) {
+        lock.acquire();
+    }
+}
+
+extern "C" fn unlock(_: *mut curl_sys::CURL, data: curl_sys::curl_lock_data, userptr: *mut c_void) {
+    let locks = unsafe {
+        &*(userptr as *const Locks)
+    };
+
+    if let Some(lock) = locks.get(data) {
+        lock.release();
+    }
+}
@@ -0,0 +1,100 @@
+use curl;
+use std::path::{Path, PathBuf};
+
+
+/// A client certificate for authenticating the client to servers that require it.
+#[derive(Clone, Debug)]
+pub struct ClientCertificate {
+    format: Format,
+    path: PathBuf,
+    private_key: Option<PathBuf>,
+    password: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Format {
+    Pem,
+    Pkcs12,
+}
+
+impl ClientCertificate {
+    /// Use a certificate stored in a PEM file.
+    ///
+    /// If the private key is stored in a separate PEM file, provide its path as `private_key`. A `password` is only
+    /// needed if the private key is encrypted.
+    pub fn pem<P: Into<PathBuf>>(path: P, private_key: Option<PathBuf>, password: Option<String>) -> ClientCertificate {
+        ClientCertificate {
+            format: Format::Pem,
+            path: path.into(),
+            private_key: private_key,
+            password: password,
+        }
+    }
+
+    /// Use a certificate and private key stored in a PKCS#12 archive, optionally protected with a password.
+    pub fn pkcs12<P: Into<PathBuf>>(path: P, password: Option<String>) -> ClientCertificate {
+        ClientCertificate {
+            format: Format::Pkcs12,
+            path: path.into(),
+            private_key: None,
+            password: password,
+        }
+    }
+
+    /// Get the paths to all files used by this certificate.
+    pub(crate) fn paths(&self) -> Vec<&Path> {
+        let mut paths = vec![self.path.as_path()];
+        paths.extend(self.private_key.as_ref().map(PathBuf::as_path));
+        paths
+    }
+
+    /// Configure an easy handle to use this certificate.
+    pub(crate) fn apply<H>(&self, easy: &mut curl::easy::Easy2<H>) -> Result<(), curl::Error> {
+        easy.ssl_cert(&self.path)?;
+
+        match self.format {
+            Format::Pem => {
+                easy.ssl_cert_type("PEM")?;
+
+                if let Some(ref key) = self.private_key {
+                    easy.ssl_key(key)?;
+                    easy.ssl_key_type("PEM")?;
+                }
+            }
+            Format::Pkcs12 => {
+                easy.ssl_cert_type("P12")?;
+            }
+        }
+
+        if let Some(ref password) = self.password {
+            easy.key_password(password)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+/// A version of the TLS protocol.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SslVersion {
+    /// TLS 1.0.
+    Tlsv10,
+    /// TLS 1.1.
+    Tlsv11,
+    /// TLS 1.2.
+    Tlsv12,
+    /// TLS 1.3.
+    Tlsv13,
+}
+
+impl From<SslVersion> for curl::easy::SslVersion {
+    fn from(version: SslVersion) -> curl::easy::SslVersion {
+        match version {
+            SslVersion::Tlsv10 => curl::easy::SslVersion::Tlsv10,
+            SslVersion::Tlsv11 => curl::easy::SslVersion::Tlsv11,
+            SslVersion::Tlsv12 => curl::easy::SslVersion::Tlsv12,
+            SslVersion::Tlsv13 => curl::easy::SslVersion::Tlsv13,
+        }
+    }
+}
@@ -1,294 +1,1044 @@
 use ringtail::ByteBuffer;
 use curl;
 use curl::easy::InfoType;
-use http;
+use curl_sys;
+use http::{self, Uri};
 use log;
-use std::cell::RefCell;
+use response::{EffectiveMethod, EffectiveUri, Informational, Metrics, Redirects, RemoteAddr, Timings, Trailers};
+use retry::{self, RetryPolicy};
+use futures::{Async, Poll};
+use futures::task::AtomicTask;
 use std::io;
-use std::io::Read;
+use std::ffi::CStr;
+use std::io::{Read, Seek, SeekFrom};
 use std::mem;
-use std::rc::Rc;
+use std::net::{IpAddr, SocketAddr};
+use std::os::raw::{c_char, c_double, c_long};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
 use std::str;
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use super::*;
 
 
-const DEFAULT_TIMEOUT_MS: u64 = 1000;
+/// Maximum number of response body bytes to buffer before pausing the transfer until the reader catches up.
+const MAX_BUFFER_SIZE: usize = 64 * 1024;
 
+/// Leaves the response to a proxy `CONNECT` request out of the headers passed to the header callback. Available since
+/// libcurl 7.54.0, but not exposed by `curl_sys`.
+const CURLOPT_SUPPRESS_CONNECT_HEADERS: curl_sys::CURLoption = curl_sys::CURLOPTTYPE_LONG + 265;
 
-/// A low-level reusable HTTP client with a single connection.
+/// Source of unique transfer IDs.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+
+/// Create a new curl easy handle for executing the given request.
 ///
-/// Transports are stateful objects that can only perform one request at a time.
-pub struct Transport {
-    /// A curl multi handle used to execute transfers.
-    multi: curl::multi::Multi,
-    /// A curl easy handle for configuring requests. Lazily initialized.
-    handle: Option<Handle>,
-    /// Protocol and connection options.
-    options: Options,
-    /// Contains the current request and response data.
-    data: Rc<RefCell<Data>>,
+/// The returned transfer can be used to wait for the response once the handle has been handed off to an agent.
+pub fn create_handle(request: Request, options: &Options) -> Result<(curl::easy::Easy2<Collector>, Arc<Transfer>), Error> {
+    let transfer = Arc::new(Transfer {
+        id: NEXT_ID.fetch_add(1, Ordering::SeqCst),
+        state: Mutex::new(State {
+            response: Some(http::response::Builder::new()),
+            header_complete: false,
+            buffer: ByteBuffer::new(),
+            paused: false,
+            complete: false,
+            error: None,
+        }),
+        condvar: Condvar::new(),
+        task: AtomicTask::new(),
+    });
+
+    let (mut parts, mut body) = request.into_parts();
+
+    // Read bodies of unknown size into memory if we are not allowed to send them chunked.
+    if body.len().is_none() && !options.chunked_uploads() {
+        let content_type = body.content_type();
+        let mut bytes = Vec::new();
+        body.read_to_end(&mut bytes)?;
+
+        body = match content_type {
+            Some(content_type) => Body::from(bytes).with_content_type(content_type),
+            None => Body::from(bytes),
+        };
+    }
+
+    let mut easy = curl::easy::Easy2::new(Collector {
+        transfer: transfer.clone(),
+        request_body: Body::Empty,
+        handle: ptr::null_mut(),
+        method: parts.method.clone(),
+        headers: http::HeaderMap::new(),
+        redirect_policy: options.redirect_policy(),
+        allow_redirect_downgrade: options.allow_redirect_downgrade(),
+        auto_referer: options.auto_referer(),
+        auth_schemes: options.authentication().map_or(&[], Authentication::challenge_schemes),
+        status: None,
+        redirects: Vec::new(),
+        redirect_time: Duration::from_secs(0),
+        metrics: Metrics::default(),
+        informational: Vec::new(),
+        trailers: Trailers::default(),
+        trailer_fields: http::HeaderMap::new(),
+        unexpected_response: false,
+        progress: options.progress().cloned(),
+        decompress: options.automatic_decompression(),
+        connect_timeout_first: options.timeout().map_or(true, |timeout| options.connect_timeout() < timeout),
+        timeout: options.timeout(),
+        read_timeout: options.read_timeout(),
+        first_byte_timeout: options.first_byte_timeout(),
+        first_byte_deadline: None,
+        retry_policy: options.retry_policy(),
+        attempts: 1,
+        request_sent: body.is_empty(),
+        bytes_sent: 0,
+        followup: None,
+        challenge: None,
+        failure: None,
+    });
+    easy.get_mut().handle = easy.raw();
+
+    // Enable or disable debug tracing.
+    easy.verbose(log_enabled!(log::Level::Trace))?;
+
+    easy.signal(false)?;
+
+    // Configure connection based on our options struct.
+    if let Some(timeout) = options.timeout() {
+        easy.timeout(timeout)?;
+    }
+    easy.connect_timeout(options.connect_timeout())?;
+    if let Some(timeout) = options.read_timeout() {
+        // Curl fails a transfer that stays below the speed limit for too long, but only counts whole seconds.
+        let seconds = timeout.as_secs() + if timeout.subsec_nanos() > 0 { 1 } else { 0 };
+        easy.low_speed_limit(1)?;
+        easy.low_speed_time(Duration::from_secs(seconds))?;
+    }
+    easy.tcp_nodelay(options.tcp_nodelay())?;
+    if let Some(interval) = options.tcp_keepalive() {
+        easy.tcp_keepalive(true)?;
+        easy.tcp_keepintvl(interval)?;
+    }
+    if let Some(timeout) = options.connection_idle_timeout() {
+        easy.maxage_conn(timeout)?;
+    }
+
+    // Let curl advertise and decode all content encodings that it supports.
+    if options.automatic_decompression() {
+        easy.accept_encoding("")?;
+    }
+
+    // Enable the cookie engine. Cookies are stored in the client's cookie jar rather than in a file.
+    easy.cookie_file("")?;
+
+    // Only ask curl for progress updates if someone is listening for them.
+    easy.progress(options.progress().is_some())?;
+
+    // Redirects are followed by the agent rather than by curl, so that the redirect policy can decide which ones to
+    // follow before the new location is contacted.
+    easy.follow_location(false)?;
+
+    if let Some(ssl_cipher_list) = options.ssl_cipher_list() {
+        easy.ssl_cipher_list(ssl_cipher_list)?;
+    }
+
+    // Configure TLS.
+    if let Some(path) = options.ssl_ca_certificate() {
+        easy.cainfo(path)?;
+    }
+    if let Some(path) = options.ssl_ca_path() {
+        easy.capath(path)?;
+    }
+    if let Some(certificate) = options.ssl_client_certificate() {
+        certificate.apply(&mut easy)?;
+    }
+    if let Some(version) = options.ssl_min_version() {
+        easy.ssl_version(version.into())?;
+    }
+    if let Some(pin) = options.ssl_pinned_public_key() {
+        easy.pinned_public_key(pin)?;
+    }
+    if options.danger_accept_invalid_certs() {
+        warn!("certificate verification is disabled for request to {}", parts.uri);
+        easy.ssl_verify_peer(false)?;
+        easy.ssl_verify_host(false)?;
+    }
+
+    // Set a preferred HTTP version to negotiate.
+    if let Some(version) = options.preferred_http_version() {
+        easy.http_version(match version {
+            http::Version::HTTP_10 => curl::easy::HttpVersion::V10,
+            http::Version::HTTP_11 => curl::easy::HttpVersion::V11,
+            http::Version::HTTP_2 => curl::easy::HttpVersion::V2,
+            _ => curl::easy::HttpVersion::Any,
+        })?;
+    }
+
+    // Set a proxy to use.
+    if let Some(proxy) = options.proxy() {
+        easy.proxy(&format!("{}", proxy))?;
+    }
+
+    // A proxy may also be picked up from the environment, so always keep the response to a tunneling request through
+    // it from being mistaken for the response. Versions of libcurl that do not know the option just ignore it.
+    unsafe {
+        curl_sys::curl_easy_setopt(easy.raw(), CURLOPT_SUPPRESS_CONNECT_HEADERS, 1 as c_long);
+    }
+
+    // Set the request data according to the request given.
+    easy.custom_request(parts.method.as_str())?;
+    easy.url(&format!("{}", parts.uri))?;
+
+    // Let the body describe its own media type, unless the request already does.
+    if !parts.headers.contains_key(http::header::CONTENT_TYPE) {
+        if let Some(content_type) = body.content_type() {
+            parts.headers.insert(http::header::CONTENT_TYPE, content_type.parse().map_err(http::Error::from)?);
+        }
+    }
+
+    if let Some(authentication) = options.authentication() {
+        authentication.apply(&mut easy, &mut parts.headers)?;
+    }
+    easy.http_headers(header_list(&parts.headers)?)?;
+
+    // Set the request body.
+    if !body.is_empty() {
+        easy.upload(true)?;
+
+        // Tell curl the size of the body if we know it, so that it is sent with a `Content-Length` instead of chunked.
+        if let Some(len) = body.len() {
+            easy.in_filesize(len)?;
+        }
+    }
+    easy.get_mut().request_body = body;
+    easy.get_mut().headers = parts.headers;
+
+    Ok((easy, transfer))
 }
 
-/// Wrapper for the various states of a curl easy handle.
-enum Handle {
-    Ready(curl::easy::Easy2<Collector>),
-    Active(curl::multi::Easy2Handle<Collector>),
+/// Prepare an easy handle whose transfer has stopped with the given result to be executed again, if a redirect is to
+/// be followed or the request is to be retried.
+///
+/// Returns how long to wait before adding the handle to the agent again, or `None` if the transfer should be completed
+/// with the result instead.
+pub fn prepare_resend(easy: &mut curl::easy::Easy2<Collector>, result: &Result<(), curl::Error>) -> Result<Option<Duration>, Error> {
+    match (easy.get_mut().followup.take(), result) {
+        (Some(Followup::Redirect(redirect)), &Ok(())) => {
+            follow_redirect(easy, redirect)?;
+            Ok(Some(Duration::from_secs(0)))
+        }
+        (Some(Followup::Redirect(_)), &Err(_)) => {
+            // The redirect response was not received completely, so treat it as any other failed attempt.
+            easy.get_mut().redirects.pop();
+            Ok(easy.get_mut().prepare_retry(result, None))
+        }
+        (Some(Followup::Retry(retry_after)), _) => {
+            Ok(easy.get_mut().prepare_retry(result, Some(retry_after)))
+        }
+        (None, _) => {
+            Ok(easy.get_mut().prepare_retry(result, None))
+        }
+    }
 }
 
-struct Data {
-    /// Request body to be sent.
-    request_body: Body,
-    /// Builder for the response object.
-    response: http::response::Builder,
-    /// Indicates if the header has been read completely.
-    header_complete: bool,
-    /// Temporary buffer for the response body.
-    buffer: ByteBuffer,
+/// Reconfigure an easy handle to send the request to the location it was redirected to.
+fn follow_redirect(easy: &mut curl::easy::Easy2<Collector>, redirect: Redirect) -> Result<(), Error> {
+    let cross_origin = !is_same_origin(&redirect.from, &redirect.to);
+    let downgrade = redirect.from.scheme_str() == Some("https") && redirect.to.scheme_str() != Some("https");
+    let mut rebuild_headers = cross_origin;
+
+    debug!("following {} redirect from {} to {}", redirect.status, redirect.from, redirect.to);
+
+    {
+        let collector = easy.get_mut();
+
+        // The redirect hop counts towards the redirect time of the final response.
+        if let Some(time) = collector.getinfo_duration(curl_sys::CURLINFO_TOTAL_TIME) {
+            collector.redirect_time += time;
+        }
+
+        // Like browsers do, switch to a GET request without a body after a 303 response, or after a 301 or 302
+        // response to a POST request.
+        let change_to_get = match redirect.status.as_u16() {
+            303 => collector.method != http::Method::HEAD,
+            301 | 302 => collector.method == http::Method::POST,
+            _ => false,
+        };
+
+        if change_to_get {
+            collector.method = http::Method::GET;
+            collector.request_body = Body::Empty;
+            collector.headers.remove(http::header::CONTENT_LENGTH);
+            collector.headers.remove(http::header::CONTENT_TYPE);
+            collector.headers.remove(http::header::TRANSFER_ENCODING);
+            rebuild_headers = true;
+        } else if collector.request_body.seek(SeekFrom::Start(0)).is_err() {
+            let error = Error::from(ErrorKind::RequestBodyError(Some(String::from("request body is a stream that cannot be rewound to be sent again"))));
+            return Err(error.with_phase(Phase::SendRequest));
+        }
+        collector.request_sent = collector.request_body.is_empty();
+        collector.bytes_sent = 0;
+
+        // Never leak credentials to a different origin.
+        if cross_origin {
+            collector.headers.remove(http::header::AUTHORIZATION);
+            collector.headers.remove(http::header::COOKIE);
+        }
+    }
+
+    if easy.get_ref().method == http::Method::GET {
+        easy.upload(false)?;
+        easy.custom_request("GET")?;
+    }
+
+    if rebuild_headers {
+        let headers = header_list(&easy.get_ref().headers)?;
+        easy.http_headers(headers)?;
+    }
+
+    if cross_origin {
+        unsafe {
+            clear_option(easy.raw(), curl_sys::CURLOPT_USERNAME);
+            clear_option(easy.raw(), curl_sys::CURLOPT_PASSWORD);
+        }
+    }
+
+    if easy.get_ref().auto_referer {
+        if downgrade {
+            unsafe {
+                clear_option(easy.raw(), curl_sys::CURLOPT_REFERER);
+            }
+        } else {
+            easy.referer(&format!("{}", redirect.from))?;
+        }
+    }
+
+    easy.url(&format!("{}", redirect.to))?;
+
+    Ok(())
 }
 
-impl Default for Transport {
-    fn default() -> Self {
-        Transport::with_options(Options::default())
+/// Build a curl header list from the given request headers.
+fn header_list(headers: &http::HeaderMap) -> Result<curl::easy::List, curl::Error> {
+    let mut list = curl::easy::List::new();
+
+    for (name, value) in headers.iter() {
+        let header = format!("{}: {}", name.as_str(), value.to_str().unwrap());
+        list.append(&header)?;
     }
+
+    Ok(list)
 }
 
-impl Transport {
-    /// Create a new transport with the given options.
-    pub fn with_options(options: Options) -> Transport {
-        let data = Rc::new(RefCell::new(Data {
-            request_body: Body::default(),
-            response: http::response::Builder::new(),
-            header_complete: false,
-            buffer: ByteBuffer::new(),
-        }));
+/// Unset a string option of an easy handle, which the safe curl bindings do not allow.
+unsafe fn clear_option(handle: *mut curl_sys::CURL, option: curl_sys::CURLoption) {
+    curl_sys::curl_easy_setopt(handle, option, ptr::null::<c_char>());
+}
 
-        Transport {
-            multi: curl::multi::Multi::new(),
-            handle: None,
-            options: options,
-            data: data,
+/// Resolve the value of a `Location` header, which may be a relative reference, against the URI of the request that
+/// returned it, as described in RFC 3986, section 5.2.
+fn resolve_location(base: &Uri, location: &str) -> Option<Uri> {
+    // Fragments are never sent to the server, so leave them out.
+    let reference = location.split('#').next().unwrap_or("");
+    let (mut reference, query) = match reference.find('?') {
+        Some(i) => (&reference[..i], Some(&reference[i + 1..])),
+        None => (reference, None),
+    };
+
+    let mut scheme = base.scheme_str()?;
+    let mut authority = base.authority_part()?.as_str();
+
+    // Only absolute URIs with a host can be followed.
+    if let Some((reference_scheme, rest)) = split_scheme(reference) {
+        if !rest.starts_with("//") {
+            return None;
         }
+        scheme = reference_scheme;
+        reference = rest;
     }
 
-    /// Check if the transport is ready to execute a new request.
-    #[inline]
-    pub fn is_ready(&self) -> bool {
-        !self.is_active()
+    let (path, query) = if reference.starts_with("//") {
+        let rest = &reference[2..];
+        let end = rest.find('/').unwrap_or(rest.len());
+        authority = &rest[..end];
+        (remove_dot_segments(&rest[end..]), query)
+    } else if reference.is_empty() {
+        // A reference without a path keeps the base path, and the base query unless it has a query of its own.
+        (base.path().to_owned(), query.or_else(|| base.query()))
+    } else if reference.starts_with('/') {
+        (remove_dot_segments(reference), query)
+    } else {
+        // A relative path replaces the last segment of the base path.
+        let base_path = base.path();
+        let directory = &base_path[..base_path.rfind('/').map_or(0, |i| i + 1)];
+        (remove_dot_segments(&format!("{}{}", if directory.is_empty() { "/" } else { directory }, reference)), query)
+    };
+
+    let mut resolved = format!("{}://{}{}", scheme, authority, if path.is_empty() { "/" } else { &path });
+    if let Some(query) = query {
+        resolved.push('?');
+        resolved.push_str(query);
     }
 
-    /// Check if the transport is already executing a request.
-    #[inline]
-    pub fn is_active(&self) -> bool {
-        if let Some(Handle::Active(_)) = self.handle {
-            true
-        } else {
-            false
+    resolved.parse().ok()
+}
+
+/// Split the scheme from a URI reference, if it has one.
+fn split_scheme(reference: &str) -> Option<(&str, &str)> {
+    let colon = reference.find(':')?;
+    let scheme = &reference[..colon];
+
+    let valid = scheme.chars().next().map_or(false, |c| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+
+    if valid {
+        Some((scheme, &reference[colon + 1..]))
+    } else {
+        None
+    }
+}
+
+/// Remove `.` and `..` segments from an absolute path.
+fn remove_dot_segments(path: &str) -> String {
+    let mut output = Vec::new();
+    let mut segments = path.split('/').peekable();
+
+    while let Some(segment) = segments.next() {
+        let last = segments.peek().is_none();
+
+        match segment {
+            "." => {}
+            // Never remove the empty segment before the leading slash.
+            ".." => if output.len() > 1 {
+                output.pop();
+            },
+            segment => {
+                output.push(segment);
+                continue;
+            }
+        }
+
+        // A path ending in a dot segment refers to a directory.
+        if last {
+            output.push("");
         }
     }
 
-    /// Execute a new request.
+    output.join("/")
+}
+
+/// Check if two URIs have the same scheme, host and port.
+fn is_same_origin(a: &Uri, b: &Uri) -> bool {
+    a.scheme_str() == b.scheme_str() && a.host() == b.host() && effective_port(a) == effective_port(b)
+}
+
+fn effective_port(uri: &Uri) -> Option<u16> {
+    uri.port_u16().or_else(|| match uri.scheme_str() {
+        Some("http") => Some(80),
+        Some("https") => Some(443),
+        _ => None,
+    })
+}
+
+
+/// The state of a single request-response cycle, shared between the agent thread executing the transfer and the
+/// threads waiting for the response.
+pub struct Transfer {
+    /// A unique ID for this transfer.
+    id: usize,
+    /// Contains the response data received so far.
+    state: Mutex<State>,
+    /// Notifies threads blocked on reading the response body.
+    condvar: Condvar,
+    /// Notifies the task waiting for the response header.
+    task: AtomicTask,
+}
+
+struct State {
+    /// Builder for the response object. Taken once the response has been returned.
+    response: Option<http::response::Builder>,
+    /// Indicates if the header has been read completely.
+    header_complete: bool,
+    /// Temporary buffer for the response body.
+    buffer: ByteBuffer,
+    /// Indicates if the transfer was paused because the buffer is full.
+    paused: bool,
+    /// Indicates if the transfer has stopped.
+    complete: bool,
+    /// The error the transfer stopped with, if any.
+    error: Option<Error>,
+}
+
+impl Transfer {
+    /// Get the unique ID of this transfer.
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Check if the transfer has stopped.
+    pub fn is_complete(&self) -> bool {
+        self.state.lock().unwrap().complete
+    }
+
+    /// Poll for the response header to be received.
     ///
-    /// If another transfer is already underway, `Error::TransportBusy` will be returned.
-    pub fn execute(&mut self, request: Request) -> Result<http::response::Builder, Error> {
-        self.begin_request(request)?;
+    /// If the transfer fails before the response header is complete, the error is returned instead.
+    pub fn poll_response(&self) -> Poll<http::response::Builder, Error> {
+        // Register first so that we do not miss a notification sent after we check the state.
+        self.task.register();
+
+        let mut state = self.state.lock().unwrap();
 
-        // Wait for the headers to be read, or for the request to be aborted.
-        while self.is_active() && !self.data.borrow().header_complete {
-            self.dispatch()?;
+        if state.header_complete {
+            if let Some(builder) = state.response.take() {
+                return Ok(Async::Ready(builder));
+            }
         }
 
-        Ok(mem::replace(&mut self.data.borrow_mut().response, http::response::Builder::new()))
+        if state.complete {
+            return Err(state.error.take().unwrap_or_else(|| ErrorKind::NoResponse.into()));
+        }
+
+        Ok(Async::NotReady)
     }
 
-    /// Cancel the current request.
+    /// Read bytes from the response body, blocking until bytes arrive or the transfer is complete.
     ///
-    /// Returns `true` if the request was canceled, or `false` if there was no active request to cancel.
-    pub fn cancel(&mut self) -> Result<bool, Error> {
-        if self.is_active() {
-            // Reset the curl handle.
-            self.end_request()?;
+    /// Returns the number of bytes read, and whether the transfer was paused and needs to be resumed.
+    pub fn read(&self, dst: &mut [u8]) -> io::Result<(usize, bool)> {
+        let mut state = self.state.lock().unwrap();
 
-            Ok(true)
-        } else {
-            Ok(false)
+        while state.buffer.is_empty() && !state.complete {
+            state = self.condvar.wait(state).unwrap();
         }
-    }
 
-    /// Begin a new request.
-    fn begin_request(&mut self, request: Request) -> Result<(), Error> {
-        // Prepare the easy handle.
-        let mut easy = match self.handle.take() {
-            // We're already engaged in a different request.
-            Some(Handle::Active(_)) => {
-                return Err(Error::TransportBusy);
+        if state.buffer.is_empty() {
+            if let Some(e) = state.error.take() {
+                return Err(e.into());
             }
-            // Re-use an already created handle.
-            Some(Handle::Ready(mut easy)) => {
-                easy.reset();
-                easy
-            }
-            // Initialize a new handle.
-            None => {
-                curl::easy::Easy2::new(Collector {
-                    data: self.data.clone(),
-                })
+        }
+
+        // Copy bytes from the internal buffer to the given one.
+        let len = state.buffer.read(dst)?;
+        let unpause = state.paused;
+        state.paused = false;
+
+        Ok((len, unpause))
+    }
+
+    /// Mark the transfer as stopped, with the given result.
+    pub fn complete(&self, result: Result<(), Error>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.complete = true;
+            state.error = result.err();
+        }
+
+        self.notify();
+    }
+
+    fn notify(&self) {
+        self.condvar.notify_all();
+        self.task.notify();
+    }
+}
+
+
+/// Receives callbacks from curl and incrementally constructs a response.
+pub struct Collector {
+    /// The transfer being executed.
+    transfer: Arc<Transfer>,
+    /// Request body to be sent.
+    request_body: Body,
+    /// The easy handle this collector belongs to, used to query transfer info from within callbacks.
+    handle: *mut curl_sys::CURL,
+    /// Method of the request currently being sent.
+    method: http::Method,
+    /// Headers of the request currently being sent.
+    headers: http::HeaderMap,
+    /// Policy for following redirect responses.
+    redirect_policy: RedirectPolicy,
+    /// Indicates if redirects from HTTPS to HTTP may be followed.
+    allow_redirect_downgrade: bool,
+    /// Indicates if the referer should be set when following redirects.
+    auto_referer: bool,
+    /// Schemes of authentication challenges that curl answers by sending the request again.
+    auth_schemes: &'static [&'static str],
+    /// Status code of the response header currently being received.
+    status: Option<http::StatusCode>,
+    /// Redirect responses that have been followed so far.
+    redirects: Vec<http::Response<()>>,
+    /// Total time spent on redirect responses that have been followed so far.
+    redirect_time: Duration,
+    /// Timing information for the transfer.
+    metrics: Metrics,
+    /// Informational responses received before the response currently being received.
+    informational: Vec<http::Response<()>>,
+    /// Trailers of the response, published once the transfer is complete.
+    trailers: Trailers,
+    /// Trailer fields received so far.
+    trailer_fields: http::HeaderMap,
+    /// Indicates if curl has started reporting another response after the response header was published.
+    unexpected_response: bool,
+    /// Callback to report progress to.
+    progress: Option<ProgressCallback>,
+    /// Indicates if curl decodes the response body.
+    decompress: bool,
+    /// Indicates if the connect timeout expires before the overall timeout does.
+    connect_timeout_first: bool,
+    /// Maximum time allowed for the request-response cycle.
+    timeout: Option<Duration>,
+    /// Maximum time to go without transferring any data, enforced by curl.
+    read_timeout: Option<Duration>,
+    /// Maximum time to wait for the first byte of the response.
+    first_byte_timeout: Option<Duration>,
+    /// When the current attempt fails if no response has started arriving by then.
+    first_byte_deadline: Option<Instant>,
+    /// Policy for retrying the request if it fails.
+    retry_policy: Option<RetryPolicy>,
+    /// Number of times the request has been attempted so far, including the current attempt.
+    attempts: u32,
+    /// Indicates if the request body has been sent completely in the current attempt.
+    request_sent: bool,
+    /// Number of bytes of the request body sent in the current attempt.
+    bytes_sent: u64,
+    /// What to do once the transfer stops, if the response being received is discarded rather than returned.
+    followup: Option<Followup>,
+    /// An authentication challenge that is held back until we know whether curl is going to answer it.
+    challenge: Option<http::response::Builder>,
+    /// An error to fail the transfer with instead of the one reported by curl, if we aborted it ourselves.
+    failure: Option<Error>,
+}
+
+/// Describes how a request will be sent again once its current transfer stops.
+enum Followup {
+    /// Follow a redirect.
+    Redirect(Redirect),
+    /// Retry the request, after waiting as long as the server asked, if it did.
+    Retry(Option<Duration>),
+}
+
+// The raw easy handle is only used from within callbacks and by the thread executing the transfer, which is the only
+// thread allowed to use the handle anyway.
+unsafe impl Send for Collector {}
+
+impl Collector {
+    /// Get the transfer this collector is receiving data for.
+    pub fn transfer(&self) -> &Arc<Transfer> {
+        &self.transfer
+    }
+
+    /// Complete the transfer with the result reported by curl.
+    pub fn complete(&mut self, result: Result<(), curl::Error>) {
+        self.update_metrics(true);
+
+        let failure = self.failure.take();
+        let result = result.map_err(|e| failure.unwrap_or_else(|| self.convert_error(e)));
+
+        if result.is_ok() {
+            // Curl did not answer the last authentication challenge, and it had no body.
+            if let Some(builder) = self.challenge.take() {
+                self.publish_response(builder);
             }
-        };
 
-        // Enable or disable debug tracing.
-        easy.verbose(log_enabled!(log::Level::Trace))?;
+            self.trailers.set(mem::replace(&mut self.trailer_fields, http::HeaderMap::new()));
+        }
 
-        easy.max_connects(1)?;
-        easy.signal(false)?;
+        self.finish(result);
+    }
 
-        // Configure connection based on our options struct.
-        if let Some(timeout) = self.options.timeout {
-            easy.timeout(timeout)?;
+    /// Convert an error reported by curl, telling apart which timeout was reached.
+    fn convert_error(&self, error: curl::Error) -> Error {
+        let timed_out = error.is_operation_timedout();
+        let error = Error::from(error);
+
+        if !timed_out {
+            return error;
+        }
+
+        // Curl reports the connect, read and overall timeouts with the same code. It only checks the read timeout once
+        // connected, and reaching it before the response header is complete means the server has yet to respond.
+        match self.phase() {
+            Phase::NameLookup | Phase::Connect | Phase::TlsHandshake => if self.connect_timeout_first {
+                return error.with_kind(ErrorKind::ConnectTimeout);
+            },
+            phase => if self.read_timeout.is_some() && !self.overall_timeout_reached() {
+                return error.with_kind(match phase {
+                    Phase::ReadBody => ErrorKind::ReadTimeout,
+                    _ => ErrorKind::FirstByteTimeout,
+                });
+            },
         }
-        easy.connect_timeout(self.options.connect_timeout)?;
-        easy.tcp_nodelay(self.options.tcp_nodelay)?;
-        if let Some(interval) = self.options.tcp_keepalive {
-            easy.tcp_keepalive(true)?;
-            easy.tcp_keepintvl(interval)?;
+
+        error
+    }
+
+    /// Check if the current attempt has been running for as long as the overall timeout allows.
+    fn overall_timeout_reached(&self) -> bool {
+        match (self.timeout, self.getinfo_duration(curl_sys::CURLINFO_TOTAL_TIME)) {
+            (Some(timeout), Some(elapsed)) => elapsed >= timeout,
+            _ => false,
         }
+    }
 
-        // Configure redirects.
-        match self.options.redirect_policy {
-            RedirectPolicy::None => {
-                easy.follow_location(false)?;
-            }
-            RedirectPolicy::Follow => {
-                easy.follow_location(true)?;
+    /// Start the first byte timeout for a new attempt. Called when the transfer is handed to curl.
+    pub fn begin_attempt(&mut self) {
+        self.first_byte_deadline = self.first_byte_timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    /// Get the time at which the current attempt fails if no response has started arriving by then.
+    pub fn first_byte_deadline(&self) -> Option<Instant> {
+        self.first_byte_deadline
+    }
+
+    /// Fail the current attempt with the given error once curl has been stopped, instead of the error curl reports.
+    pub fn abort(&mut self, error: Error) {
+        self.first_byte_deadline = None;
+        self.failure = Some(error);
+    }
+
+    /// Complete the transfer with the given result, recording which request failed and where if it is an error.
+    pub fn finish(&self, result: Result<(), Error>) {
+        let result = result.map_err(|error| {
+            let error = error.with_phase(self.phase());
+
+            match self.getinfo_string(curl_sys::CURLINFO_EFFECTIVE_URL).and_then(|s| s.parse::<Uri>().ok()) {
+                Some(uri) => error.with_request(&self.method, &uri),
+                None => error,
             }
-            RedirectPolicy::Limit(max) => {
-                easy.follow_location(true)?;
-                easy.max_redirections(max)?;
+        });
+
+        self.transfer.complete(result);
+    }
+
+    /// Work out which phase of the transfer the current attempt has reached.
+    fn phase(&self) -> Phase {
+        if self.transfer.state.lock().unwrap().header_complete {
+            return Phase::ReadBody;
+        }
+
+        // Curl reports zero for the times of phases that have not finished. Some versions record the pretransfer time
+        // when a transfer fails while connecting, so the phases are checked in order.
+        let finished = |info| self.getinfo_duration(info).map_or(false, |time| time > Duration::from_secs(0));
+
+        if !finished(curl_sys::CURLINFO_NAMELOOKUP_TIME) {
+            Phase::NameLookup
+        } else if !finished(curl_sys::CURLINFO_CONNECT_TIME) {
+            Phase::Connect
+        } else if !finished(curl_sys::CURLINFO_PRETRANSFER_TIME) {
+            Phase::TlsHandshake
+        } else if self.request_sent {
+            Phase::AwaitResponse
+        } else {
+            Phase::SendRequest
+        }
+    }
+
+    /// Prepare to retry the transfer after it stopped with the given result, if the retry policy allows it.
+    ///
+    /// If the server asked us to retry, `retry_after` contains how long it asked us to wait.
+    fn prepare_retry(&mut self, result: &Result<(), curl::Error>, retry_after: Option<Option<Duration>>) -> Option<Duration> {
+        let retry = match *result {
+            Ok(()) => retry_after.is_some(),
+            Err(ref e) => {
+                let transient = is_transient(e) || self.failure.as_ref().map_or(false, |failure| failure.kind().is_timeout());
+                retry_after.is_some() || (transient && !self.transfer.state.lock().unwrap().header_complete && self.can_retry())
             }
+        };
+
+        if !retry {
+            return None;
         }
 
-        if let Some(ref ssl_cipher_list) = self.options.ssl_cipher_list {
-            easy.ssl_cipher_list(ssl_cipher_list.as_str())?;
+        // Rewind the request body so that it can be sent again.
+        if self.request_body.seek(SeekFrom::Start(0)).is_err() {
+            return None;
         }
 
-        // Set a preferred HTTP version to negotiate.
-        if let Some(version) = self.options.preferred_http_version {
-            easy.http_version(match version {
-                http::Version::HTTP_10 => curl::easy::HttpVersion::V10,
-                http::Version::HTTP_11 => curl::easy::HttpVersion::V11,
-                http::Version::HTTP_2 => curl::easy::HttpVersion::V2,
-                _ => curl::easy::HttpVersion::Any,
-            })?;
+        let delay = self.retry_policy?.delay(self.attempts, retry_after.unwrap_or(None));
+        debug!("retrying transfer {} after attempt {} in {:?}", self.transfer.id(), self.attempts, delay);
+
+        self.attempts += 1;
+        self.request_sent = self.request_body.is_empty();
+        self.bytes_sent = 0;
+        self.failure = None;
+        self.status = None;
+        self.challenge = None;
+        self.transfer.state.lock().unwrap().response = Some(http::response::Builder::new());
+
+        Some(delay)
+    }
+
+    /// Check if the retry policy allows another attempt, and if the request body can be sent again.
+    fn can_retry(&self) -> bool {
+        self.request_body.is_rewindable() && self.retry_policy.map_or(false, |policy| policy.allows(&self.method, self.attempts))
+    }
+
+    /// Update the timing metrics for the transfer. The total time is only recorded once the transfer is complete.
+    fn update_metrics(&self, complete: bool) {
+        let total = if complete {
+            self.getinfo_time(curl_sys::CURLINFO_TOTAL_TIME)
+        } else {
+            None
+        };
+
+        self.metrics.update(Timings {
+            name_lookup: self.getinfo_time(curl_sys::CURLINFO_NAMELOOKUP_TIME).unwrap_or_default(),
+            connect: self.getinfo_time(curl_sys::CURLINFO_CONNECT_TIME).unwrap_or_default(),
+            tls_handshake: self.getinfo_time(curl_sys::CURLINFO_APPCONNECT_TIME).unwrap_or_default(),
+            first_byte: self.getinfo_time(curl_sys::CURLINFO_STARTTRANSFER_TIME).unwrap_or_default(),
+            redirect: self.redirect_time,
+            total: total,
+        });
+    }
+
+    /// Get a time reported by curl for the current request, measured from the start of the first request instead.
+    ///
+    /// Curl reports zero for phases that did not happen, such as the TLS handshake of a plain HTTP request, and those
+    /// are left as zero.
+    fn getinfo_time(&self, info: curl_sys::CURLINFO) -> Option<Duration> {
+        self.getinfo_duration(info).map(|time| if time > Duration::from_secs(0) {
+            time + self.redirect_time
+        } else {
+            time
+        })
+    }
+
+    /// Handle the end of a response header.
+    ///
+    /// Curl reports the headers of informational responses as well, and redirect and retryable responses may be
+    /// discarded, so this is not necessarily the end of the header of the response that will be returned. Returns
+    /// `false` if the transfer should be aborted.
+    fn complete_header(&mut self) -> bool {
+        let status = self.status.take();
+
+        // Do not hold on to the lock while deciding what to do with the response, as that may call back into user code.
+        // A new builder is created for the next response, if any, once its status line is received.
+        let mut builder = self.transfer.state.lock().unwrap().response.take().unwrap_or_else(http::response::Builder::new);
+
+        // Informational responses are always followed by another response.
+        if status.map_or(false, |status| status.is_informational()) {
+            if let Ok(response) = builder.body(()) {
+                self.informational.push(response);
+            }
+            return true;
         }
 
-        // Set a proxy to use.
-        if let Some(ref proxy) = self.options.proxy {
-            easy.proxy(&format!("{}", proxy))?;
+        let effective_uri = self.getinfo_string(curl_sys::CURLINFO_EFFECTIVE_URL).and_then(|s| s.parse::<Uri>().ok());
+        if let Some(ref uri) = effective_uri {
+            builder.extension(EffectiveUri(uri.clone()));
         }
+        builder.extension(EffectiveMethod(self.method.clone()));
 
-        // Set the request data according to the request given.
-        easy.custom_request(request.method().as_str())?;
-        easy.url(&format!("{}", request.uri()))?;
+        // Stash redirect responses that we are going to follow, and wait for the transfer to stop.
+        let redirect = match (status, effective_uri) {
+            (Some(status), Some(from)) if is_followed_redirect(status) => builder.headers_ref()
+                .and_then(|headers| headers.get(http::header::LOCATION))
+                .and_then(|value| value.to_str().ok())
+                .and_then(|location| resolve_location(&from, location))
+                .map(|to| Redirect {
+                    status: status,
+                    from: from,
+                    to: to,
+                }),
+            _ => None,
+        };
 
-        let mut headers = curl::easy::List::new();
-        for (name, value) in request.headers() {
-            let header = format!("{}: {}", name.as_str(), value.to_str().unwrap());
-            headers.append(&header)?;
+        if let Some(redirect) = redirect {
+            match self.should_follow(&redirect) {
+                Ok(true) => {
+                    if let Ok(response) = builder.body(()) {
+                        self.redirects.push(response);
+                    }
+                    self.followup = Some(Followup::Redirect(redirect));
+                    self.informational.clear();
+                    return true;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    self.failure = Some(e);
+                    return false;
+                }
+            }
         }
-        easy.http_headers(headers)?;
 
-        // Set the request body.
-        let body = request.into_parts().1;
-        if !body.is_empty() {
-            easy.upload(true)?;
+        // Discard responses asking us to try again later if we are going to retry, and wait for the transfer to stop.
+        if status.map_or(false, retry::is_retryable_status) && self.can_retry() {
+            let retry_after = builder.headers_ref()
+                .and_then(|headers| headers.get(http::header::RETRY_AFTER))
+                .and_then(|value| value.to_str().ok())
+                .and_then(retry::parse_retry_after);
+            self.followup = Some(Followup::Retry(retry_after));
+            self.informational.clear();
+            return true;
         }
-        self.data.borrow_mut().request_body = body;
 
-        // Finalize the easy handle state and attach it to the multi handle to be executed.
-        let easy = self.multi.add2(easy)?;
-        self.handle = Some(Handle::Active(easy));
+        // Hold back authentication challenges, since curl answers those it has credentials for by sending the request
+        // again. If it does not, the challenge is returned once its body arrives or the transfer stops.
+        if status.map_or(false, |status| self.is_auth_challenge(status, &builder)) {
+            self.challenge = Some(builder);
+            return true;
+        }
 
-        // Reset buffers and other temporary data.
-        self.data.borrow_mut().header_complete = false;
-        self.data.borrow_mut().buffer.clear();
+        self.publish_response(builder);
 
-        Ok(())
+        true
     }
 
-    /// Reset the transport to the ready state.
-    ///
-    /// Note that this will abort the current request.
-    fn end_request(&mut self) -> Result<(), Error> {
-        // Reset the curl easy handle.
-        self.handle = match self.handle.take() {
-            Some(Handle::Active(easy)) => {
-                let easy = self.multi.remove2(easy)?;
-                Some(Handle::Ready(easy))
-            },
-            handle => handle,
+    /// Check if a response is an authentication challenge for one of the schemes that curl answers.
+    fn is_auth_challenge(&self, status: http::StatusCode, builder: &http::response::Builder) -> bool {
+        let header = match status {
+            http::StatusCode::UNAUTHORIZED => http::header::WWW_AUTHENTICATE,
+            http::StatusCode::PROXY_AUTHENTICATION_REQUIRED => http::header::PROXY_AUTHENTICATE,
+            _ => return false,
         };
 
-        Ok(())
+        // Each header may contain several comma-separated challenges, each starting with a scheme name followed by
+        // parameters.
+        builder.headers_ref().map_or(false, |headers| headers.get_all(header).iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|challenge| challenge.split_whitespace().next())
+            .any(|scheme| !scheme.contains('=') && self.auth_schemes.iter().any(|s| scheme.eq_ignore_ascii_case(s))))
     }
 
-    /// Dispatch reads and writes, blocking the current thread if necessary.
-    fn dispatch(&mut self) -> Result<(), Error> {
-        if self.is_active() {
-            // Determine the blocking timeout value.
-            let timeout = self.multi.get_timeout()?.unwrap_or(Duration::from_millis(DEFAULT_TIMEOUT_MS));
+    /// Make a response header available to the threads waiting for it, as the response to return.
+    fn publish_response(&mut self, mut builder: http::response::Builder) {
+        let ip = self.getinfo_string(curl_sys::CURLINFO_PRIMARY_IP).and_then(|s| s.parse::<IpAddr>().ok());
+        let port = self.getinfo_long(curl_sys::CURLINFO_PRIMARY_PORT);
+        if let (Some(ip), Some(port)) = (ip, port) {
+            builder.extension(RemoteAddr(SocketAddr::new(ip, port as u16)));
+        }
 
-            // Block until activity is detected or the timeout passes.
-            trace!("waiting with timeout of {:?}", timeout);
-            self.multi.wait(&mut [], timeout)?;
+        // The headers describing the encoded body no longer apply once curl has decoded it.
+        if self.decompress {
+            if let Some(headers) = builder.headers_mut() {
+                let encoded = headers.get(http::header::CONTENT_ENCODING).map_or(false, |value| value != "identity");
 
-            // Perform any pending reads or writes. If `perform()` returns zero, then the current transfer is complete.
-            if self.multi.perform()? == 0 {
-                // The current transfer has stopped, but that does not mean it succeeded. Check the transfer status now
-                // and return any errors we find.
-                let mut result = None;
+                if encoded {
+                    headers.remove(http::header::CONTENT_ENCODING);
+                    headers.remove(http::header::CONTENT_LENGTH);
+                }
+            }
+        }
 
-                // Read messages before we detach the easy handle, or the messages will be discarded.
-                self.multi.messages(|message| {
-                    trace!("curl message: {:?}", message);
-                    if let Some(Err(e)) = message.result() {
-                        result = Some(e);
-                    }
-                });
+        self.update_metrics(false);
+        builder.extension(Redirects(mem::replace(&mut self.redirects, Vec::new())));
+        builder.extension(self.metrics.clone());
+        builder.extension(Informational(mem::replace(&mut self.informational, Vec::new())));
+        builder.extension(self.trailers.clone());
 
-                self.end_request()?;
+        {
+            let mut state = self.transfer.state.lock().unwrap();
+            state.response = Some(builder);
+            state.header_complete = true;
+        }
+        self.transfer.notify();
+    }
 
-                if let Some(e) = result {
-                    return Err(e.into());
-                }
+    /// Decide whether to follow a redirect, according to the redirect policy.
+    fn should_follow(&self, redirect: &Redirect) -> Result<bool, Error> {
+        if self.redirect_policy == RedirectPolicy::None {
+            return Ok(false);
+        }
+
+        // Only follow redirects to HTTP and HTTPS, and never downgrade from HTTPS unless allowed to.
+        match redirect.to.scheme_str() {
+            Some("https") => {}
+            Some("http") if redirect.from.scheme_str() != Some("https") || self.allow_redirect_downgrade => {}
+            _ => {
+                warn!("not following redirect from {} to {}", redirect.from, redirect.to);
+                return Ok(false);
             }
         }
 
-        Ok(())
+        match self.redirect_policy {
+            RedirectPolicy::None => Ok(false),
+            RedirectPolicy::Follow => Ok(true),
+            RedirectPolicy::Limit(max) if self.redirects.len() >= max as usize => Err(ErrorKind::TooManyRedirects.into()),
+            RedirectPolicy::Limit(_) => Ok(true),
+            RedirectPolicy::Custom(ref callback) => catch_panic(|| callback(redirect)),
+        }
     }
-}
 
-impl Read for Transport {
-    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
-        // Block until bytes arrive in the buffer or the transfer is complete.
-        while self.data.borrow().buffer.is_empty() && self.is_active() {
-            // Attempt to fill the buffer with more bytes.
-            self.dispatch()?;
+    fn getinfo_string(&self, info: curl_sys::CURLINFO) -> Option<String> {
+        let mut value: *const c_char = ptr::null();
+
+        unsafe {
+            if curl_sys::curl_easy_getinfo(self.handle, info, &mut value) != curl_sys::CURLE_OK || value.is_null() {
+                return None;
+            }
+
+            CStr::from_ptr(value).to_str().ok().map(String::from)
         }
+    }
 
-        // Copy bytes from the internal buffer to the given one.
-        self.data.borrow_mut().buffer.read(dst)
+    fn getinfo_long(&self, info: curl_sys::CURLINFO) -> Option<c_long> {
+        let mut value: c_long = 0;
+
+        unsafe {
+            if curl_sys::curl_easy_getinfo(self.handle, info, &mut value) != curl_sys::CURLE_OK {
+                return None;
+            }
+        }
+
+        Some(value)
+    }
+
+    fn getinfo_duration(&self, info: curl_sys::CURLINFO) -> Option<Duration> {
+        let mut value: c_double = 0.0;
+
+        unsafe {
+            if curl_sys::curl_easy_getinfo(self.handle, info, &mut value) != curl_sys::CURLE_OK {
+                return None;
+            }
+        }
+
+        // Curl reports times in fractional seconds.
+        Some(Duration::new(value.trunc() as u64, (value.fract() * 1e9) as u32))
     }
 }
 
-// Even though we use Rc within the transport, all Rc references live within the Transport struct, so it is safe to move
-// the transport as a whole between threads.
-unsafe impl Send for Transport {}
+/// Check if a transfer that failed with the given error may succeed if it is attempted again.
+fn is_transient(error: &curl::Error) -> bool {
+    error.is_couldnt_connect()
+        || error.is_got_nothing()
+        || error.is_operation_timedout()
+        || error.is_send_error()
+        || error.is_recv_error()
+}
 
+/// Call a callback provided by the user, turning a panic into an error that aborts the transfer instead of letting it
+/// unwind through curl and into the agent thread.
+fn catch_panic<T, F: FnOnce() -> T>(callback: F) -> Result<T, Error> {
+    panic::catch_unwind(AssertUnwindSafe(callback)).map_err(|_| {
+        error!("callback panicked, aborting transfer");
+        ErrorKind::Aborted.into()
+    })
+}
 
-/// Receives callbacks from curl and incrementally constructs a response.
-struct Collector {
-    data: Rc<RefCell<Data>>,
+/// Parse a header line into a header name and value.
+fn parse_header(line: &str) -> Option<(http::header::HeaderName, http::header::HeaderValue)> {
+    let pos = line.find(':')?;
+    let (name, value) = line.split_at(pos);
+
+    let name = http::header::HeaderName::from_bytes(name.trim().as_bytes()).ok()?;
+    let value = http::header::HeaderValue::from_str(value[1..].trim()).ok()?;
+
+    Some((name, value))
+}
+
+/// Check if a response with the given status code is a redirect that curl would follow.
+fn is_followed_redirect(status: http::StatusCode) -> bool {
+    match status.as_u16() {
+        300 | 301 | 302 | 303 | 307 | 308 => true,
+        _ => false,
+    }
 }
 
 impl curl::easy::Handler for Collector {
@@ -299,45 +1049,82 @@ impl curl::easy::Handler for Collector {
             _  => return false,
         };
 
+        // The server has started responding.
+        self.first_byte_deadline = None;
+
         // Curl calls this function for all lines in the response not part of the response body, not just for headers.
         // We need to inspect the contents of the string in order to determine what it is and how to parse it, just as
         // if we were reading from the socket of a HTTP/1.0 or HTTP/1.1 connection ourselves.
 
-        // Is this the status line?
+        // Once the response header is complete, any further header lines are trailers sent after the body. A status
+        // line would start the header of another response instead, which cannot be delivered once the response has
+        // been published, so it is ignored along with everything after it.
+        if self.transfer.state.lock().unwrap().header_complete {
+            if line.starts_with("HTTP/") {
+                warn!("ignoring response received after the response header was complete: {:?}", line.trim_right());
+                self.unexpected_response = true;
+            } else if !self.unexpected_response {
+                if let Some((name, value)) = parse_header(line) {
+                    self.trailer_fields.append(name, value);
+                }
+            }
+
+            return true;
+        }
+
+        // Is this the status line? This is the start of a new response, which may be one of several informational
+        // responses sent before the final one.
         if line.starts_with("HTTP/") {
-            // Parse the HTTP protocol version.
-            let version = match &line[0..8] {
-                "HTTP/2.0" => http::Version::HTTP_2,
-                "HTTP/1.1" => http::Version::HTTP_11,
-                "HTTP/1.0" => http::Version::HTTP_10,
-                "HTTP/0.9" => http::Version::HTTP_09,
+            // Curl answered the authentication challenge we held back, which belonged to the previous request.
+            if self.challenge.take().is_some() {
+                self.informational.clear();
+            }
+
+            let mut parts = line.split_whitespace();
+
+            // Parse the HTTP protocol version. HTTP/2 status lines have no minor version.
+            let version = match parts.next() {
+                Some("HTTP/2") | Some("HTTP/2.0") => http::Version::HTTP_2,
+                Some("HTTP/1.1") => http::Version::HTTP_11,
+                Some("HTTP/1.0") => http::Version::HTTP_10,
+                Some("HTTP/0.9") => http::Version::HTTP_09,
                 _ => http::Version::default(),
             };
-            self.data.borrow_mut().response.version(version);
 
             // Parse the status code.
-            let status_code = match http::StatusCode::from_str(&line[9..12]) {
-                Ok(s) => s,
+            let status_code = match parts.next().map(http::StatusCode::from_str) {
+                Some(Ok(s)) => s,
                 _ => return false,
             };
-            self.data.borrow_mut().response.status(status_code);
+
+            self.status = Some(status_code);
+            self.transfer.state.lock().unwrap()
+                .response
+                .get_or_insert_with(http::response::Builder::new)
+                .version(version)
+                .status(status_code);
 
             return true;
         }
 
         // Is this a header line?
-        if let Some(pos) = line.find(":") {
-            let (name, value) = line.split_at(pos);
-            let value = value[2..].trim();
-            self.data.borrow_mut().response.header(name, value);
+        if line.contains(':') {
+            match parse_header(line) {
+                Some((name, value)) => {
+                    self.transfer.state.lock().unwrap()
+                        .response
+                        .get_or_insert_with(http::response::Builder::new)
+                        .header(name, value);
+                }
+                None => warn!("ignoring malformed header line: {:?}", line),
+            }
 
             return true;
         }
 
         // Is this the end of the response header?
         if line == "\r\n" {
-            self.data.borrow_mut().header_complete = true;
-            return true;
+            return self.complete_header();
         }
 
         // Unknown header line we don't know how to parse.
@@ -346,15 +1133,85 @@ impl curl::easy::Handler for Collector {
 
     // Gets called by curl when attempting to send bytes of the request body.
     fn read(&mut self, data: &mut [u8]) -> Result<usize, curl::easy::ReadError> {
-        self.data.borrow_mut()
-            .request_body
+        let len = self.request_body
             .read(data)
-            .map_err(|_| curl::easy::ReadError::Abort)
+            .map_err(|_| curl::easy::ReadError::Abort)?;
+
+        // Curl stops reading once it has sent as many bytes as it was told the body has.
+        self.bytes_sent += len as u64;
+        if len == 0 || self.request_body.len().map_or(false, |total| self.bytes_sent >= total) {
+            self.request_sent = true;
+        }
+
+        Ok(len)
+    }
+
+    // Gets called by curl when it needs to send the request body again, such as when following a redirect or responding
+    // to an authentication challenge.
+    fn seek(&mut self, whence: SeekFrom) -> curl::easy::SeekResult {
+        match self.request_body.seek(whence) {
+            Ok(_) => {
+                self.request_sent = false;
+                self.bytes_sent = 0;
+                curl::easy::SeekResult::Ok
+            }
+            Err(_) => curl::easy::SeekResult::CantSeek,
+        }
     }
 
     // Gets called by curl when bytes from the response body are received.
     fn write(&mut self, data: &[u8]) -> Result<usize, curl::easy::WriteError> {
-        Ok(self.data.borrow_mut().buffer.push(data))
+        // Skip the bodies of responses we are discarding.
+        if self.followup.is_some() {
+            return Ok(data.len());
+        }
+
+        // Curl skips the body of an authentication challenge that it answers, so this challenge is the final response.
+        if let Some(builder) = self.challenge.take() {
+            self.publish_response(builder);
+        }
+
+        let mut state = self.transfer.state.lock().unwrap();
+
+        // Pause the transfer if the reader is not keeping up. Curl will give us the same data again once the transfer
+        // is resumed.
+        if state.buffer.len() >= MAX_BUFFER_SIZE {
+            state.paused = true;
+            return Err(curl::easy::WriteError::Pause);
+        }
+
+        let len = state.buffer.push(data);
+        drop(state);
+        self.transfer.notify();
+
+        Ok(len)
+    }
+
+    // Gets called by curl periodically while the transfer is in progress.
+    fn progress(&mut self, dltotal: f64, dlnow: f64, ultotal: f64, ulnow: f64) -> bool {
+        if let Some(ref callback) = self.progress {
+            // Curl reports a total of zero if the total size is not known.
+            let progress = Progress {
+                upload_total: if ultotal > 0.0 { Some(ultotal as u64) } else { None },
+                uploaded: ulnow as u64,
+                download_total: if dltotal > 0.0 { Some(dltotal as u64) } else { None },
+                downloaded: dlnow as u64,
+            };
+
+            match catch_panic(|| callback.call(&progress)) {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.failure = Some(ErrorKind::Aborted.into());
+                    return false;
+                }
+                Err(e) => {
+                    self.failure = Some(e);
+                    return false;
+                }
+            }
+        }
+
+        true
     }
 
     fn debug(&mut self, kind: InfoType, data: &[u8]) {
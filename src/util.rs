@@ -0,0 +1,9 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+
+/// Get a pseudo-random number. Each new `RandomState` uses different keys, so even hashing nothing gives a different
+/// result every time.
+pub fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
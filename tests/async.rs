@@ -0,0 +1,135 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::futures::Future;
+use chttp::futures::future::join_all;
+use chttp::http::Request;
+use chttp::{Body, Error, ErrorKind, Options, ProgressCallback};
+use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn concurrent_requests_share_one_client() {
+    let url = common::serve(|request| {
+        rouille::Response::text(request.url())
+    });
+
+    let client = chttp::Client::default();
+
+    // Start all requests before waiting on any of them. The test server can leave new connections waiting behind ones
+    // that are kept alive, so have it close each connection once it has responded.
+    let futures = (0..10)
+        .map(|i| {
+            let request = Request::get(format!("{}/{}", url, i))
+                .header("Connection", "close")
+                .body(Body::Empty)
+                .unwrap();
+            client.send_async(request)
+        })
+        .collect::<Vec<_>>();
+
+    let responses = join_all(futures).wait().unwrap();
+
+    for (i, mut response) in responses.into_iter().enumerate() {
+        assert_eq!(response.body_mut().text().unwrap(), format!("/{}", i));
+    }
+}
+
+#[test]
+fn large_response_body_is_streamed_completely() {
+    let url = common::serve(|_| {
+        rouille::Response::from_data("application/octet-stream", vec![7u8; 4 * 1024 * 1024])
+    });
+
+    let mut response = chttp::get(&url).unwrap();
+
+    let mut body = Vec::new();
+    response.body_mut().read_to_end(&mut body).unwrap();
+
+    assert_eq!(body.len(), 4 * 1024 * 1024);
+    assert!(body.iter().all(|&byte| byte == 7));
+}
+
+#[test]
+fn requests_wait_for_a_connection_when_the_limit_is_reached() {
+    let url = common::serve(|_| {
+        thread::sleep(Duration::from_millis(100));
+        rouille::Response::text("hello world")
+    });
+
+    let client = chttp::Client::builder()
+        .max_connections_per_host(Some(1))
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+
+    let futures = (0..4)
+        .map(|_| {
+            let request = Request::get(url.clone()).body(Body::Empty).unwrap();
+            client.send_async(request)
+        })
+        .collect::<Vec<_>>();
+
+    for mut response in join_all(futures).wait().unwrap() {
+        assert_eq!(response.body_mut().text().unwrap(), "hello world");
+    }
+
+    // Only one request can be served at a time over a single connection.
+    assert!(start.elapsed() >= Duration::from_millis(400));
+}
+
+#[test]
+fn requests_succeed_with_shared_cache() {
+    let url = common::serve(|request| {
+        rouille::Response::text(request.url())
+    });
+
+    let client = chttp::Client::builder()
+        .shared_cache(true)
+        .build()
+        .unwrap();
+
+    let futures = (0..4)
+        .map(|i| {
+            let request = Request::get(format!("{}/{}", url, i)).body(Body::Empty).unwrap();
+            client.send_async(request)
+        })
+        .collect::<Vec<_>>();
+
+    for (i, mut response) in join_all(futures).wait().unwrap().into_iter().enumerate() {
+        assert_eq!(response.body_mut().text().unwrap(), format!("/{}", i));
+    }
+}
+
+#[test]
+fn panicking_callback_fails_only_its_own_request() {
+    let url = common::serve(|_| {
+        rouille::Response::text("hello world")
+    });
+
+    let panicked = Arc::new(AtomicBool::new(false));
+    let client = chttp::Client::builder()
+        .options(Options::default().with_progress(Some(ProgressCallback::new(move |_| {
+            if !panicked.swap(true, Ordering::SeqCst) {
+                panic!("progress callback failed");
+            }
+            true
+        }))))
+        .build()
+        .unwrap();
+
+    assert!(match client.get(&url).as_ref().map_err(Error::kind) {
+        Err(&ErrorKind::Aborted) => true,
+        _ => false,
+    });
+
+    // The agent thread keeps serving other requests.
+    let mut response = client.get(&url).unwrap();
+    assert_eq!(response.body_mut().text().unwrap(), "hello world");
+}
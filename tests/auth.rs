@@ -0,0 +1,143 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::{Authentication, Options};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+
+#[test]
+fn basic_credentials_are_sent() {
+    let url = common::echo_server(&["Authorization"]);
+
+    let client = chttp::Client::builder()
+        .options(Options::default().with_authentication(Some(Authentication::Basic {
+            username: String::from("Aladdin"),
+            password: String::from("open sesame"),
+        })))
+        .build()
+        .unwrap();
+
+    let mut response = client.get(&url).unwrap();
+    assert_eq!(response.body_mut().text().unwrap(), "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==\n");
+}
+
+#[test]
+fn bearer_token_is_sent() {
+    let url = common::echo_server(&["Authorization"]);
+
+    let request = chttp::http::Request::get(url)
+        .extension(Options::default().with_authentication(Some(Authentication::Bearer(String::from("abc123")))))
+        .body(chttp::Body::Empty)
+        .unwrap();
+
+    let mut response = chttp::send(request).unwrap();
+    assert_eq!(response.body_mut().text().unwrap(), "Bearer abc123\n");
+}
+
+/// Start a server that challenges requests to authenticate with Digest until they come with credentials for the given
+/// user, and then echoes the request body.
+fn start_digest_server(username: &'static str) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+
+            // Serve requests until the client closes the connection.
+            loop {
+                let mut authorization = None;
+                let mut content_length = 0;
+
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 {
+                        break;
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+
+                    let lower = line.to_lowercase();
+                    if lower.starts_with("authorization:") {
+                        authorization = Some(line[14..].trim().to_owned());
+                    } else if lower.starts_with("content-length:") {
+                        content_length = line[15..].trim().parse().unwrap();
+                    }
+                }
+
+                let mut body = vec![0; content_length];
+                if reader.read_exact(&mut body).is_err() {
+                    break;
+                }
+
+                let response = match authorization {
+                    Some(ref value) if value.starts_with("Digest") && value.contains(&format!("username=\"{}\"", username)) => {
+                        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), String::from_utf8(body).unwrap())
+                    }
+                    _ => String::from("HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Digest realm=\"test\", nonce=\"abc\", qop=\"auth\"\r\nContent-Length: 6\r\n\r\ndenied"),
+                };
+
+                if reader.get_mut().write_all(response.as_bytes()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    addr
+}
+
+fn client(authentication: Authentication) -> chttp::Client {
+    chttp::Client::builder()
+        .options(Options::default().with_authentication(Some(authentication)))
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn digest_challenge_is_answered_with_the_request_body() {
+    let addr = start_digest_server("alice");
+    let client = client(Authentication::Digest {
+        username: String::from("alice"),
+        password: String::from("secret"),
+    });
+
+    let mut response = client.post(&format!("http://{}", addr), "hello world").unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_mut().text().unwrap(), "hello world");
+}
+
+#[test]
+fn request_body_is_sent_again_when_any_scheme_is_challenged() {
+    let addr = start_digest_server("alice");
+
+    // The first request is sent with its body and without credentials, so the body has to be rewound.
+    let client = client(Authentication::Any {
+        username: String::from("alice"),
+        password: String::from("secret"),
+    });
+
+    let mut response = client.post(&format!("http://{}", addr), "hello world").unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_mut().text().unwrap(), "hello world");
+}
+
+#[test]
+fn rejected_digest_credentials_return_the_last_challenge() {
+    let addr = start_digest_server("alice");
+    let client = client(Authentication::Digest {
+        username: String::from("mallory"),
+        password: String::from("secret"),
+    });
+
+    let mut response = client.post(&format!("http://{}", addr), "hello world").unwrap();
+
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.body_mut().text().unwrap(), "denied");
+}
@@ -0,0 +1,52 @@
+extern crate chttp;
+
+use chttp::{Client, Error, ErrorKind, Options};
+
+#[test]
+fn build_fails_for_unsupported_proxy_scheme() {
+    let options = Options::default().with_proxy(Some("ftp://localhost:2121".parse().unwrap()));
+
+    assert!(match Client::builder().options(options).build().as_ref().map_err(Error::kind) {
+        Err(&ErrorKind::InvalidConfiguration(_)) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn build_fails_for_malformed_cipher_list() {
+    let options = Options::default().with_ssl_cipher_list(Some(String::from("ECDHE-RSA-AES128-GCM-SHA256:<script>")));
+
+    assert!(match Client::builder().options(options).build().as_ref().map_err(Error::kind) {
+        Err(&ErrorKind::InvalidConfiguration(_)) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn build_succeeds_for_valid_options() {
+    let options = Options::default()
+        .with_proxy(Some("socks5h://localhost:1080".parse().unwrap()))
+        .with_ssl_cipher_list(Some(String::from("ECDHE-RSA-AES128-GCM-SHA256:!aNULL")));
+
+    assert!(Client::builder().options(options).max_connections(Some(4)).build().is_ok());
+}
+
+#[test]
+fn build_fails_for_missing_ca_certificate() {
+    let options = Options::default().with_ssl_ca_certificate(Some("/nonexistent/ca.pem".into()));
+
+    assert!(match Client::builder().options(options).build().as_ref().map_err(Error::kind) {
+        Err(&ErrorKind::InvalidConfiguration(_)) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn build_fails_for_malformed_public_key_pin() {
+    let options = Options::default().with_ssl_pinned_public_key(Some(String::from("sha256//abc;md5//def")));
+
+    assert!(match Client::builder().options(options).build().as_ref().map_err(Error::kind) {
+        Err(&ErrorKind::InvalidConfiguration(_)) => true,
+        _ => false,
+    });
+}
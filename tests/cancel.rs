@@ -0,0 +1,68 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::{Body, CancellationToken, Error, ErrorKind};
+use chttp::http::Request;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn request(url: &str, token: &CancellationToken) -> chttp::Request {
+    Request::get(url)
+        .extension(token.clone())
+        .body(Body::Empty)
+        .unwrap()
+}
+
+#[test]
+fn requests_in_progress_can_be_cancelled() {
+    let url = common::serve(|_| {
+        thread::sleep(Duration::from_secs(5));
+        rouille::Response::text("too late")
+    });
+
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        canceller.cancel();
+    });
+
+    let start = Instant::now();
+    let result = chttp::send(request(&url, &token));
+
+    assert!(match result.as_ref().map_err(Error::kind) {
+        Err(&ErrorKind::Cancelled) => true,
+        _ => false,
+    });
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn requests_with_a_cancelled_token_fail_right_away() {
+    let token = CancellationToken::new();
+    token.cancel();
+
+    let result = chttp::send(request("http://127.0.0.1:1", &token));
+
+    assert!(match result.as_ref().map_err(Error::kind) {
+        Err(&ErrorKind::Cancelled) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn cancelling_aborts_reading_the_body() {
+    // Send part of a response, and then stall.
+    let url = common::serve_slowly(&[b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello"], Duration::from_secs(5));
+
+    let token = CancellationToken::new();
+    let mut response = chttp::send(request(&url, &token)).unwrap();
+    token.cancel();
+
+    assert!(match response.body_mut().text().as_ref().map_err(Error::kind) {
+        Err(&ErrorKind::Cancelled) => true,
+        _ => false,
+    });
+}
@@ -0,0 +1,41 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::{ErrorKind, ResponseBodyExt};
+
+/// Start a server that responds with the given content type and body.
+fn serve(content_type: &'static str, body: &'static [u8]) -> String {
+    common::serve(move |_| rouille::Response::from_data(content_type, body))
+}
+
+#[test]
+fn text_is_decoded_using_charset_from_header() {
+    let url = serve("text/html; charset=\"ISO-8859-1\"", b"caf\xe9");
+
+    let mut response = chttp::get(&url).unwrap();
+
+    assert_eq!(response.text().unwrap(), "café");
+}
+
+#[test]
+fn byte_order_mark_takes_precedence_over_header() {
+    let url = serve("text/plain; charset=shift_jis", b"\xff\xfeh\x00i\x00");
+
+    let mut response = chttp::get(&url).unwrap();
+
+    assert_eq!(response.text().unwrap(), "hi");
+}
+
+#[test]
+fn invalid_text_fails_unless_lossy() {
+    let url = serve("text/plain", b"caf\xe9");
+
+    match chttp::get(&url).unwrap().text().unwrap_err().kind() {
+        &ErrorKind::InvalidUtf8 => {}
+        kind => panic!("expected invalid UTF-8 error, got {:?}", kind),
+    }
+
+    assert_eq!(chttp::get(&url).unwrap().text_lossy().unwrap(), "caf\u{fffd}");
+}
@@ -0,0 +1,69 @@
+// Not every test uses every helper.
+#![allow(dead_code)]
+
+use rouille;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+/// Start a server that handles requests with the given function, and return its base URL.
+pub fn serve<F>(handler: F) -> String
+    where F: Fn(&rouille::Request) -> rouille::Response + Send + Sync + 'static
+{
+    let server = rouille::Server::new("127.0.0.1:0", handler).unwrap();
+    let addr = server.server_addr();
+    thread::spawn(move || server.run());
+
+    format!("http://{}", addr)
+}
+
+/// Start a server that responds to every request with the values of the given request headers and the request body, as
+/// described by `echo`.
+pub fn echo_server(headers: &'static [&'static str]) -> String {
+    serve(move |request| rouille::Response::text(echo(request, headers)))
+}
+
+/// Describe a request by the values of the given headers, one per line and `none` for missing headers, followed by a
+/// line with the request body.
+pub fn echo(request: &rouille::Request, headers: &[&str]) -> String {
+    let mut body = String::new();
+    request.data().unwrap().read_to_string(&mut body).unwrap();
+
+    let mut lines = headers.iter()
+        .map(|name| request.header(name).unwrap_or("none").to_owned())
+        .collect::<Vec<_>>();
+    lines.push(body);
+
+    lines.join("\n")
+}
+
+/// Start a server that accepts a single connection, reads the request, and leaves writing the raw response to the given
+/// function. Returns the base URL of the server.
+pub fn serve_raw<F>(respond: F) -> String
+    where F: FnOnce(&mut TcpStream) + Send + 'static
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.read(&mut [0; 1024]).unwrap();
+        respond(&mut stream);
+    });
+
+    format!("http://{}", addr)
+}
+
+/// Start a server that sends a raw response to a single request in the given pieces, waiting after each one.
+pub fn serve_slowly(pieces: &'static [&'static [u8]], delay: Duration) -> String {
+    serve_raw(move |stream| {
+        for piece in pieces {
+            // The client may give up before the whole response has been sent.
+            if stream.write_all(piece).and_then(|_| stream.flush()).is_err() {
+                break;
+            }
+            thread::sleep(delay);
+        }
+    })
+}
@@ -0,0 +1,69 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use std::env;
+use std::fs;
+
+/// Start a server that sets a session cookie on `/login`, and echoes the cookies of all other requests.
+fn start_server() -> String {
+    common::serve(|request| {
+        match request.url().as_str() {
+            "/login" => rouille::Response::text("welcome")
+                .with_additional_header("Set-Cookie", "session=abc123; Path=/"),
+            _ => rouille::Response::text(common::echo(request, &["Cookie"])),
+        }
+    })
+}
+
+#[test]
+fn cookies_are_stored_and_sent_back() {
+    let url = start_server();
+    let client = chttp::Client::default();
+
+    client.get(&format!("{}/login", url)).unwrap();
+
+    let mut response = client.get(&format!("{}/check", url)).unwrap();
+    assert_eq!(response.body_mut().text().unwrap(), "session=abc123\n");
+
+    let cookies = client.cookie_jar().cookies().unwrap();
+    assert_eq!(cookies.len(), 1);
+    assert_eq!(cookies[0].name(), "session");
+    assert_eq!(cookies[0].value(), "abc123");
+
+    // Cookies are not shared between clients.
+    let mut response = chttp::get(&format!("{}/check", url)).unwrap();
+    assert_eq!(response.body_mut().text().unwrap(), "none\n");
+}
+
+#[test]
+fn cookies_can_be_cleared() {
+    let url = start_server();
+    let client = chttp::Client::default();
+
+    client.get(&format!("{}/login", url)).unwrap();
+    client.cookie_jar().clear().unwrap();
+
+    assert!(client.cookie_jar().cookies().unwrap().is_empty());
+
+    let mut response = client.get(&format!("{}/check", url)).unwrap();
+    assert_eq!(response.body_mut().text().unwrap(), "none\n");
+}
+
+#[test]
+fn cookies_can_be_saved_and_loaded() {
+    let url = start_server();
+    let path = env::temp_dir().join(format!("chttp-cookies-{}.txt", url.rsplit(':').next().unwrap()));
+
+    let client = chttp::Client::default();
+    client.get(&format!("{}/login", url)).unwrap();
+    client.cookie_jar().save(&path).unwrap();
+
+    let client = chttp::Client::default();
+    client.cookie_jar().load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut response = client.get(&format!("{}/check", url)).unwrap();
+    assert_eq!(response.body_mut().text().unwrap(), "session=abc123\n");
+}
@@ -0,0 +1,52 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::Options;
+use std::io::Read;
+
+/// "hello world" compressed with gzip.
+static GZIPPED: &[u8] = &[
+    0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0x28, 0xcf, 0x2f,
+    0xca, 0x49, 0x01, 0x00, 0x85, 0x11, 0x4a, 0x0d, 0x0b, 0x00, 0x00, 0x00,
+];
+
+/// Start a server that responds with "hello world", compressed with gzip if the request accepts it.
+fn start_server() -> String {
+    common::serve(|request| {
+        if request.header("Accept-Encoding").map_or(false, |value| value.contains("gzip")) {
+            rouille::Response::from_data("text/plain", GZIPPED).with_additional_header("Content-Encoding", "gzip")
+        } else {
+            rouille::Response::text("hello world")
+        }
+    })
+}
+
+#[test]
+fn gzip_responses_are_decoded_automatically() {
+    let url = start_server();
+
+    let mut response = chttp::get(&url).unwrap();
+
+    assert!(response.headers().get("Content-Encoding").is_none());
+    assert_eq!(response.body_mut().text().unwrap(), "hello world");
+}
+
+#[test]
+fn decompression_can_be_disabled_per_request() {
+    let url = start_server();
+
+    let request = chttp::http::Request::get(url)
+        .header("Accept-Encoding", "gzip")
+        .extension(Options::default().with_automatic_decompression(false))
+        .body(chttp::Body::Empty)
+        .unwrap();
+
+    let mut response = chttp::send(request).unwrap();
+    let mut body = Vec::new();
+    response.body_mut().read_to_end(&mut body).unwrap();
+
+    assert_eq!(response.headers()["Content-Encoding"], "gzip");
+    assert_eq!(body, GZIPPED);
+}
@@ -0,0 +1,74 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::{ErrorKind, Options, Phase};
+use std::error::Error as StdError;
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn connect_errors_include_request_and_phase() {
+    // Find a port that nobody is listening on.
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let url = format!("http://{}/", addr);
+
+    let error = chttp::get(&url).err().unwrap();
+
+    assert!(match error.kind() {
+        &ErrorKind::ConnectFailed => true,
+        _ => false,
+    });
+    assert_eq!(error.method(), Some(&chttp::http::Method::GET));
+    assert_eq!(error.uri().map(ToString::to_string), Some(url.clone()));
+    assert_eq!(error.phase(), Some(Phase::Connect));
+    assert_eq!(error.curl_code(), Some(7));
+    assert!(error.source().is_some());
+    assert!(error.to_string().contains(&format!("GET {}", url)));
+}
+
+#[test]
+fn timeout_while_waiting_for_response_is_reported() {
+    let url = common::serve(|_| {
+        thread::sleep(Duration::from_secs(3));
+        rouille::Response::text("hello world")
+    });
+
+    let client = chttp::Client::builder()
+        .options(Options::default().with_timeout(Some(Duration::from_secs(1))))
+        .build()
+        .unwrap();
+
+    let error = client.post(&url, "hello").err().unwrap();
+
+    assert!(match error.kind() {
+        &ErrorKind::Timeout => true,
+        _ => false,
+    });
+    assert_eq!(error.method(), Some(&chttp::http::Method::POST));
+    assert_eq!(error.phase(), Some(Phase::AwaitResponse));
+    assert_eq!(error.curl_code(), Some(28));
+}
+
+#[test]
+fn errors_reading_the_body_keep_their_context() {
+    // Send part of a response, and then stall.
+    let url = common::serve_slowly(&[b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello"], Duration::from_secs(3));
+
+    let client = chttp::Client::builder()
+        .options(Options::default().with_timeout(Some(Duration::from_secs(1))))
+        .build()
+        .unwrap();
+
+    let mut response = client.get(&url).unwrap();
+    let error = response.body_mut().text().unwrap_err();
+
+    assert!(match error.kind() {
+        &ErrorKind::Timeout => true,
+        _ => false,
+    });
+    assert_eq!(error.phase(), Some(Phase::ReadBody));
+    assert!(error.uri().is_some());
+}
@@ -0,0 +1,42 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::Body;
+
+#[test]
+fn form_body_is_encoded_with_content_type() {
+    let url = common::echo_server(&["Content-Type"]);
+    let body = Body::form(vec![("name", "Jane Doe"), ("note", "a&b=c/ü")]);
+
+    let mut response = chttp::Client::default().post(&url, body).unwrap();
+
+    assert_eq!(
+        response.body_mut().text().unwrap(),
+        "application/x-www-form-urlencoded\nname=Jane+Doe&note=a%26b%3Dc%2F%C3%BC"
+    );
+}
+
+#[test]
+fn explicit_content_type_header_takes_precedence() {
+    let url = common::echo_server(&["Content-Type"]);
+    let request = chttp::http::Request::post(url)
+        .header("Content-Type", "text/plain")
+        .body(Body::form(vec![("a", "b")]))
+        .unwrap();
+
+    let mut response = chttp::Client::default().send(request).unwrap();
+
+    assert_eq!(response.body_mut().text().unwrap(), "text/plain\na=b");
+}
+
+#[test]
+fn query_parameters_are_appended() {
+    let url = common::serve(|request| rouille::Response::text(request.raw_query_string()));
+    let uri = chttp::form::append_query(&format!("{}/search?lang=en#top", url), vec![("q", "rust http")]);
+
+    let mut response = chttp::Client::default().get(&uri).unwrap();
+
+    assert_eq!(response.body_mut().text().unwrap(), "lang=en&q=rust+http");
+}
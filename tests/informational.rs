@@ -0,0 +1,36 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::ResponseExt;
+use std::io::Write;
+
+#[test]
+fn informational_responses_are_kept_separately() {
+    let url = common::serve_raw(|stream| stream.write_all(b"HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\nX-Final: yes\r\n\r\nok").unwrap());
+
+    let mut response = chttp::get(&url).unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["X-Final"], "yes");
+    assert!(!response.headers().contains_key("Link"));
+    assert_eq!(response.body_mut().text().unwrap(), "ok");
+
+    let informational = response.informational();
+    assert_eq!(informational.len(), 1);
+    assert_eq!(informational[0].status(), 103);
+    assert_eq!(informational[0].headers()["Link"], "</style.css>; rel=preload");
+}
+
+#[test]
+fn trailers_are_available_after_the_body() {
+    let url = common::serve_raw(|stream| stream.write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: X-Checksum\r\n\r\n5\r\nhello\r\n0\r\nX-Checksum: abc123\r\n\r\n").unwrap());
+
+    let mut response = chttp::get(&url).unwrap();
+    let trailers = response.trailers().unwrap().clone();
+
+    assert_eq!(response.body_mut().text().unwrap(), "hello");
+    assert_eq!(trailers.get().unwrap()["X-Checksum"], "abc123");
+    assert!(!response.headers().contains_key("X-Checksum"));
+}
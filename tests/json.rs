@@ -0,0 +1,49 @@
+#![cfg(feature = "json")]
+
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::Body;
+use std::collections::BTreeMap;
+
+/// Start a server that responds to every request with the given body.
+fn serve(response: &'static str) -> String {
+    common::serve(move |_| rouille::Response::text(response))
+}
+
+#[test]
+fn json_body_is_sent_with_content_type() {
+    let url = common::echo_server(&["Content-Type"]);
+    let mut value = BTreeMap::new();
+    value.insert("name", "chttp");
+
+    let mut response = chttp::Client::default().post(&url, Body::from_json(&value).unwrap()).unwrap();
+
+    assert_eq!(response.body_mut().text().unwrap(), "application/json\n{\"name\":\"chttp\"}");
+}
+
+#[test]
+fn json_response_is_deserialized() {
+    let url = serve("{\"a\": 1, \"b\": 2}");
+
+    let mut response = chttp::Client::default().get(&url).unwrap();
+    let value: BTreeMap<String, u32> = response.body_mut().json().unwrap();
+
+    assert_eq!(value.get("a"), Some(&1));
+    assert_eq!(value.get("b"), Some(&2));
+}
+
+#[test]
+fn invalid_json_error_has_position() {
+    let url = serve("{\n  \"a\": oops\n}");
+
+    let mut response = chttp::Client::default().get(&url).unwrap();
+    let result = response.body_mut().json::<BTreeMap<String, u32>>();
+
+    match result.as_ref().map_err(chttp::Error::kind) {
+        Err(&chttp::ErrorKind::InvalidJson { line, column, .. }) => assert_eq!((line, column), (2, 8)),
+        _ => panic!("expected a JSON error"),
+    }
+}
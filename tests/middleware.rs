@@ -0,0 +1,90 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::{Body, Error, ErrorKind, Middleware, Request, Response};
+use std::sync::{Arc, Mutex};
+
+struct AddHeader(&'static str, &'static str);
+
+impl Middleware for AddHeader {
+    fn before(&self, request: &mut Request) -> Result<Option<Response>, Error> {
+        request.headers_mut().insert(self.0, self.1.parse().unwrap());
+        Ok(None)
+    }
+}
+
+struct Record(&'static str, Arc<Mutex<Vec<String>>>);
+
+impl Middleware for Record {
+    fn before(&self, _: &mut Request) -> Result<Option<Response>, Error> {
+        self.1.lock().unwrap().push(format!("before {}", self.0));
+        Ok(None)
+    }
+
+    fn after(&self, _: &mut Response) -> Result<(), Error> {
+        self.1.lock().unwrap().push(format!("after {}", self.0));
+        Ok(())
+    }
+}
+
+struct ShortCircuit;
+
+impl Middleware for ShortCircuit {
+    fn before(&self, _: &mut Request) -> Result<Option<Response>, Error> {
+        Ok(Some(chttp::http::Response::builder().status(418).body(Body::Empty)?))
+    }
+}
+
+struct Reject;
+
+impl Middleware for Reject {
+    fn before(&self, _: &mut Request) -> Result<Option<Response>, Error> {
+        Err(ErrorKind::InvalidConfiguration(String::from("rejected")).into())
+    }
+}
+
+#[test]
+fn middleware_can_modify_requests() {
+    let url = common::echo_server(&["X-Trace-Id"]);
+
+    let client = chttp::Client::builder()
+        .middleware(AddHeader("X-Trace-Id", "1234"))
+        .build()
+        .unwrap();
+
+    let mut response = client.get(&url).unwrap();
+    assert_eq!(response.body_mut().text().unwrap(), "1234\n");
+}
+
+#[test]
+fn middleware_runs_in_order_and_can_short_circuit() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let client = chttp::Client::builder()
+        .middleware(Record("a", log.clone()))
+        .middleware(ShortCircuit)
+        .middleware(Record("b", log.clone()))
+        .build()
+        .unwrap();
+
+    // The request never reaches the network, so the URI does not need to exist.
+    let response = client.get("http://localhost:1").unwrap();
+
+    assert_eq!(response.status(), 418);
+    assert_eq!(*log.lock().unwrap(), vec!["before a", "after a"]);
+}
+
+#[test]
+fn middleware_errors_include_the_request() {
+    let client = chttp::Client::builder()
+        .middleware(Reject)
+        .build()
+        .unwrap();
+
+    let error = client.get("http://localhost:1/rejected").err().unwrap();
+
+    assert_eq!(error.method(), Some(&chttp::http::Method::GET));
+    assert_eq!(error.uri().unwrap().path(), "/rejected");
+}
@@ -0,0 +1,51 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::multipart::{Form, Part};
+use std::env;
+use std::fs::File;
+use std::io::Write;
+
+#[test]
+fn form_with_text_and_file_is_sent_with_length() {
+    let url = common::echo_server(&["Content-Type", "Content-Length"]);
+    let path = env::temp_dir().join("chttp-multipart-test.txt");
+    File::create(&path).unwrap().write_all(b"file contents").unwrap();
+
+    let form = Form::new()
+        .text("title", "hello")
+        .file("upload", &path)
+        .unwrap();
+    let boundary = form.boundary().to_owned();
+    let len = form.len().unwrap();
+
+    let mut response = chttp::Client::default().post(&url, form).unwrap();
+    let text = response.body_mut().text().unwrap();
+
+    let expected_body = format!(
+        "--{0}\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nhello\r\n\
+         --{0}\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"chttp-multipart-test.txt\"\r\n\
+         Content-Type: application/octet-stream\r\n\r\nfile contents\r\n--{0}--\r\n",
+        boundary);
+    assert_eq!(len, expected_body.len() as u64);
+    assert_eq!(text, format!("multipart/form-data; boundary={}\n{}\n{}", boundary, len, expected_body));
+}
+
+#[test]
+fn form_with_reader_is_streamed() {
+    let url = common::echo_server(&["Content-Type", "Content-Length"]);
+    let form = Form::new()
+        .part("data", Part::reader(&b"streamed"[..]).with_filename("data.bin"));
+    let boundary = form.boundary().to_owned();
+    assert_eq!(form.len(), None);
+
+    let mut response = chttp::Client::default().post(&url, form).unwrap();
+    let text = response.body_mut().text().unwrap();
+
+    assert_eq!(text, format!(
+        "multipart/form-data; boundary={0}\nnone\n\
+         --{0}\r\nContent-Disposition: form-data; name=\"data\"; filename=\"data.bin\"\r\n\r\nstreamed\r\n--{0}--\r\n",
+        boundary));
+}
@@ -0,0 +1,70 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::{Error, ErrorKind, Options, ProgressCallback};
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn progress_is_reported_for_downloads() {
+    let url = common::serve(|_| {
+        rouille::Response::from_data("application/octet-stream", vec![0u8; 16 * 1024])
+    });
+
+    let last = Arc::new(Mutex::new(None));
+    let last_clone = last.clone();
+
+    let client = chttp::Client::builder()
+        .options(Options::default().with_progress(Some(ProgressCallback::new(move |progress| {
+            *last_clone.lock().unwrap() = Some(*progress);
+            true
+        }))))
+        .build()
+        .unwrap();
+
+    let mut response = client.get(&url).unwrap();
+    response.body_mut().read_to_end(&mut Vec::new()).unwrap();
+
+    let progress = last.lock().unwrap().unwrap();
+    assert_eq!(progress.download_total(), Some(16 * 1024));
+    assert_eq!(progress.downloaded(), 16 * 1024);
+}
+
+#[test]
+fn returning_false_from_progress_callback_aborts_request() {
+    let url = common::serve(|_| {
+        rouille::Response::text("hello world")
+    });
+
+    let request = chttp::http::Request::get(url)
+        .extension(Options::default().with_progress(Some(ProgressCallback::new(|_| false))))
+        .body(chttp::Body::Empty)
+        .unwrap();
+
+    assert!(match chttp::send(request).as_ref().map_err(Error::kind) {
+        Err(&ErrorKind::Aborted) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn request_options_can_disable_the_client_progress_callback() {
+    let url = common::serve(|_| {
+        rouille::Response::text("hello world")
+    });
+
+    let client = chttp::Client::builder()
+        .options(Options::default().with_progress(Some(ProgressCallback::new(|_| false))))
+        .build()
+        .unwrap();
+
+    let request = chttp::http::Request::get(url)
+        .extension(Options::default().with_progress(None))
+        .body(chttp::Body::Empty)
+        .unwrap();
+
+    let mut response = client.send(request).unwrap();
+    assert_eq!(response.body_mut().text().unwrap(), "hello world");
+}
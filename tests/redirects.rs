@@ -0,0 +1,118 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::{Options, RedirectPolicy, ResponseExt};
+use std::sync::{Arc, Mutex};
+
+/// Start a server that redirects `/a` to `/b`, and `/b` to the given location, and echoes the method and the
+/// `Authorization` and `Referer` headers of all other requests.
+fn start_server(location: String) -> String {
+    common::serve(move |request| {
+        match request.url().as_str() {
+            "/a" => rouille::Response::redirect_302("/b"),
+            "/b" => rouille::Response::redirect_302(location.clone()),
+            _ => rouille::Response::text(format!("{}\n{}", request.method(), common::echo(request, &["Authorization", "Referer"]))),
+        }
+    })
+}
+
+fn client(options: Options) -> chttp::Client {
+    chttp::Client::builder().options(options).build().unwrap()
+}
+
+#[test]
+fn custom_policy_can_stop_redirects() {
+    let url = start_server(String::from("/c"));
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen_by_policy = seen.clone();
+
+    let client = client(Options::default().with_redirect_policy(RedirectPolicy::custom(move |redirect| {
+        seen_by_policy.lock().unwrap().push((redirect.status().as_u16(), redirect.to().path().to_owned()));
+        redirect.to().path() != "/c"
+    })));
+
+    let response = client.get(&format!("{}/a", url)).unwrap();
+
+    assert_eq!(response.status(), 302);
+    assert_eq!(response.effective_uri().unwrap().path(), "/b");
+    assert_eq!(response.redirects().len(), 1);
+    assert_eq!(*seen.lock().unwrap(), vec![(302, String::from("/b")), (302, String::from("/c"))]);
+}
+
+#[test]
+fn limit_policy_fails_with_too_many_redirects() {
+    let url = start_server(String::from("/c"));
+    let client = client(Options::default().with_redirect_policy(RedirectPolicy::Limit(1)));
+
+    assert!(match client.get(&format!("{}/a", url)).as_ref().map_err(chttp::Error::kind) {
+        Err(&chttp::ErrorKind::TooManyRedirects) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn post_is_changed_to_get_after_found() {
+    let url = start_server(String::from("/c"));
+    let client = client(Options::default().with_redirect_policy(RedirectPolicy::Follow));
+
+    let mut response = client.post(&format!("{}/a", url), "hello").unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_mut().text().unwrap(), "GET\nnone\nnone\n");
+}
+
+#[test]
+fn authorization_is_only_sent_to_the_same_origin() {
+    let other = start_server(String::from("/c"));
+    let url = start_server(format!("{}/c", other));
+    let client = client(Options::default().with_redirect_policy(RedirectPolicy::Follow));
+
+    let request = chttp::http::Request::get(format!("{}/a", url))
+        .header("Authorization", "secret")
+        .body(chttp::Body::Empty)
+        .unwrap();
+    let mut response = client.send(request).unwrap();
+    assert_eq!(response.effective_uri().unwrap().to_string(), format!("{}/c", other));
+    assert_eq!(response.body_mut().text().unwrap(), "GET\nnone\nnone\n");
+
+    let request = chttp::http::Request::get(format!("{}/b", other))
+        .header("Authorization", "secret")
+        .body(chttp::Body::Empty)
+        .unwrap();
+    let mut response = client.send(request).unwrap();
+    assert_eq!(response.body_mut().text().unwrap(), "GET\nsecret\nnone\n");
+}
+
+#[test]
+fn auto_referer_sets_referer_when_following() {
+    let url = start_server(String::from("/c"));
+    let client = client(Options::default()
+        .with_redirect_policy(RedirectPolicy::Follow)
+        .with_auto_referer(true));
+
+    let mut response = client.get(&format!("{}/a", url)).unwrap();
+
+    assert_eq!(response.body_mut().text().unwrap(), format!("GET\nnone\n{}/b\n", url));
+}
+
+#[test]
+fn relative_locations_are_resolved_against_the_request_uri() {
+    let url = common::serve(|request| {
+        match request.raw_url() {
+            "/a/b/search" => rouille::Response::redirect_302("?page=2"),
+            "/a/b/search?page=2" => rouille::Response::redirect_302("../c/./d#top"),
+            url => rouille::Response::text(url),
+        }
+    });
+
+    let client = client(Options::default().with_redirect_policy(RedirectPolicy::Follow));
+    let mut response = client.get(&format!("{}/a/b/search", url)).unwrap();
+
+    let redirects = response.redirects().iter()
+        .map(|redirect| redirect.effective_uri().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(redirects, vec![format!("{}/a/b/search", url), format!("{}/a/b/search?page=2", url)]);
+    assert_eq!(response.body_mut().text().unwrap(), "/a/c/d");
+}
@@ -0,0 +1,90 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::{ErrorKind, Options, RedirectPolicy, ResponseBodyExt, ResponseExt};
+use std::io::{Read, Write};
+use std::sync::mpsc;
+
+#[test]
+fn response_includes_effective_uri_and_redirect_chain() {
+    let url = common::serve(|request| {
+        match request.url().as_str() {
+            "/a" => rouille::Response::redirect_302("/b"),
+            "/b" => rouille::Response::redirect_301("/c"),
+            _ => rouille::Response::text("done"),
+        }
+    });
+
+    let client = chttp::Client::builder()
+        .options(Options::default().with_redirect_policy(RedirectPolicy::Follow))
+        .build()
+        .unwrap();
+
+    let mut response = client.get(&format!("{}/a", url)).unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.effective_uri().unwrap().path(), "/c");
+    assert_eq!(response.remote_addr().map(|addr| format!("http://{}", addr)), Some(url));
+
+    let redirects = response.redirects().iter()
+        .map(|redirect| (redirect.status().as_u16(), redirect.effective_uri().unwrap().path().to_owned()))
+        .collect::<Vec<_>>();
+    assert_eq!(redirects, vec![(302, String::from("/a")), (301, String::from("/b"))]);
+
+    response.body_mut().read_to_end(&mut Vec::new()).unwrap();
+
+    let metrics = response.metrics().unwrap();
+    assert!(metrics.first_byte_time() >= metrics.connect_time());
+    assert!(metrics.total_time().unwrap() >= metrics.first_byte_time());
+}
+
+#[test]
+fn total_time_is_known_once_the_body_has_been_read() {
+    let (sender, receiver) = mpsc::channel();
+
+    let url = common::serve_raw(move |stream| {
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n").unwrap();
+
+        // Hold back the body until the client has checked the metrics.
+        receiver.recv().unwrap();
+        stream.write_all(b"done").unwrap();
+    });
+
+    let mut response = chttp::get(&url).unwrap();
+    assert!(response.metrics().unwrap().total_time().is_none());
+
+    sender.send(()).unwrap();
+    response.body_mut().read_to_end(&mut Vec::new()).unwrap();
+    assert!(response.metrics().unwrap().total_time().is_some());
+}
+
+#[test]
+fn error_for_status_includes_status_headers_and_body() {
+    let url = common::serve(|request| {
+        match request.url().as_str() {
+            "/ok" => rouille::Response::text("fine"),
+            _ => rouille::Response::text("x".repeat(10000))
+                .with_status_code(503)
+                .with_unique_header("Retry-After", "120"),
+        }
+    });
+
+    let mut response = chttp::get(&format!("{}/ok", url)).unwrap().error_for_status().unwrap();
+    assert_eq!(response.text().unwrap(), "fine");
+
+    let error = chttp::get(&format!("{}/fail", url)).unwrap().error_for_status().err().unwrap();
+
+    match error.kind() {
+        &ErrorKind::HttpStatus(ref status) => {
+            assert_eq!(status.status(), 503);
+            assert_eq!(status.headers()["Retry-After"], "120");
+            assert_eq!(status.body().len(), 4096);
+        }
+        kind => panic!("expected status error, got {:?}", kind),
+    }
+
+    assert_eq!(error.method(), Some(&chttp::http::Method::GET));
+    assert_eq!(error.uri().unwrap().path(), "/fail");
+}
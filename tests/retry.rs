@@ -0,0 +1,59 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[test]
+fn unavailable_responses_are_retried_after_delay() {
+    let count = Arc::new(AtomicUsize::new(0));
+    let server_count = count.clone();
+    let url = common::serve(move |_| {
+        if server_count.fetch_add(1, Ordering::SeqCst) == 0 {
+            rouille::Response::text("try again later")
+                .with_status_code(503)
+                .with_additional_header("Retry-After", "1")
+        } else {
+            rouille::Response::text("hello world")
+        }
+    });
+
+    let client = chttp::Client::builder()
+        .options(chttp::Options::default().with_retry_policy(Some(chttp::RetryPolicy::new(3))))
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    let mut response = client.get(&url).unwrap();
+    let mut body = String::new();
+    response.body_mut().read_to_string(&mut body).unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(body, "hello world");
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+    assert!(start.elapsed() >= Duration::from_secs(1));
+}
+
+#[test]
+fn non_idempotent_requests_are_not_retried_by_default() {
+    let count = Arc::new(AtomicUsize::new(0));
+    let server_count = count.clone();
+    let url = common::serve(move |_| {
+        server_count.fetch_add(1, Ordering::SeqCst);
+        rouille::Response::text("try again later").with_status_code(503)
+    });
+
+    let client = chttp::Client::builder()
+        .options(chttp::Options::default().with_retry_policy(Some(chttp::RetryPolicy::new(3))))
+        .build()
+        .unwrap();
+
+    let response = client.post(&url, "hello").unwrap();
+
+    assert_eq!(response.status(), 503);
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,69 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::{Body, Options, RedirectPolicy};
+use std::env;
+use std::fs::File;
+use std::io::{Cursor, Seek, SeekFrom, Write};
+
+/// Start a server that redirects `/old` to `/new`, and echoes the body of all other requests.
+fn echo_server() -> String {
+    common::serve(|request| {
+        match request.url().as_str() {
+            "/old" => rouille::Response::redirect_307("/new"),
+            _ => rouille::Response::text(common::echo(request, &[])),
+        }
+    })
+}
+
+#[test]
+fn body_is_resent_after_temporary_redirect() {
+    let url = echo_server();
+    let client = chttp::Client::builder()
+        .options(Options::default().with_redirect_policy(RedirectPolicy::Follow))
+        .build()
+        .unwrap();
+
+    let mut response = client.post(&format!("{}/old", url), "hello world").unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_mut().text().unwrap(), "hello world");
+}
+
+#[test]
+fn file_body_is_resent_from_its_starting_position() {
+    let url = echo_server();
+    let client = chttp::Client::builder()
+        .options(Options::default().with_redirect_policy(RedirectPolicy::Follow))
+        .build()
+        .unwrap();
+
+    let path = env::temp_dir().join("chttp-rewind-test.txt");
+    File::create(&path).unwrap().write_all(b"skipped hello world").unwrap();
+    let mut file = File::open(&path).unwrap();
+    file.seek(SeekFrom::Start(8)).unwrap();
+
+    let mut response = client.post(&format!("{}/old", url), file).unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_mut().text().unwrap(), "hello world");
+}
+
+#[test]
+fn streaming_body_cannot_be_resent() {
+    let url = echo_server();
+    let client = chttp::Client::builder()
+        .options(Options::default().with_redirect_policy(RedirectPolicy::Follow))
+        .build()
+        .unwrap();
+
+    let body = Body::from_reader(Cursor::new(b"hello world".to_vec()));
+    let result = client.post(&format!("{}/old", url), body);
+
+    assert!(match result.as_ref().map_err(chttp::Error::kind) {
+        Err(&chttp::ErrorKind::RequestBodyError(_)) => true,
+        _ => false,
+    });
+}
@@ -2,6 +2,8 @@ extern crate chttp;
 extern crate env_logger;
 extern crate rouille;
 
+mod common;
+
 use std::env;
 use std::time::Duration;
 use std::thread;
@@ -20,16 +22,17 @@ fn request_errors_if_read_timeout_is_reached() {
     });
 
     // Create an impatient client.
-    let mut options = chttp::Options::default();
-    options.timeout = Some(Duration::from_secs(2));
-    let client = chttp::Client::with_options(options);
+    let client = chttp::Client::builder()
+        .options(chttp::Options::default().with_timeout(Some(Duration::from_secs(2))))
+        .build()
+        .unwrap();
 
     // Send a request.
     let result = client.post("http://localhost:18080", "hello world");
 
     // Client should time-out.
-    assert!(match result {
-        Err(chttp::Error::Timeout) => true,
+    assert!(match result.as_ref().map_err(chttp::Error::kind) {
+        Err(&chttp::ErrorKind::Timeout) => true,
         _ => false,
     });
 }
@@ -38,3 +41,114 @@ fn setup() {
     env::set_var("RUST_LOG", "chttp=trace,curl=trace");
     env_logger::init();
 }
+
+#[test]
+fn request_options_override_client_timeout() {
+    let url = common::serve(|_| {
+        thread::sleep(Duration::from_secs(2));
+        rouille::Response::text("hello world")
+    });
+
+    // A patient client, but an impatient request.
+    let client = chttp::Client::builder()
+        .options(chttp::Options::default().with_timeout(Some(Duration::from_secs(10))))
+        .build()
+        .unwrap();
+
+    let request = chttp::http::Request::get(url)
+        .extension(chttp::Options::default().with_timeout(Some(Duration::from_secs(1))))
+        .body(chttp::Body::Empty)
+        .unwrap();
+
+    assert!(match client.send(request).as_ref().map_err(chttp::Error::kind) {
+        Err(&chttp::ErrorKind::Timeout) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn first_byte_timeout_is_distinct() {
+    let url = common::serve(|_| {
+        thread::sleep(Duration::from_secs(3));
+        rouille::Response::text("hello world")
+    });
+
+    let client = chttp::Client::builder()
+        .options(chttp::Options::default()
+            .with_timeout(Some(Duration::from_secs(10)))
+            .with_first_byte_timeout(Some(Duration::from_secs(1))))
+        .build()
+        .unwrap();
+
+    assert!(match client.get(&url).as_ref().map_err(chttp::Error::kind) {
+        Err(&chttp::ErrorKind::FirstByteTimeout) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn read_timeout_fails_stalled_downloads() {
+    // Send part of a response, and then stall for longer than curl takes to notice.
+    let url = common::serve_slowly(&[b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello"], Duration::from_secs(10));
+
+    let client = chttp::Client::builder()
+        .options(chttp::Options::default().with_read_timeout(Some(Duration::from_secs(1))))
+        .build()
+        .unwrap();
+
+    let mut response = client.get(&url).unwrap();
+
+    assert!(match response.body_mut().text().as_ref().map_err(chttp::Error::kind) {
+        Err(&chttp::ErrorKind::ReadTimeout) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn read_timeout_does_not_limit_downloads_that_make_progress() {
+    let url = common::serve_slowly(
+        &[b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhe", b"ll", b"ow", b"or", b"ld"],
+        Duration::from_millis(500));
+
+    let client = chttp::Client::builder()
+        .options(chttp::Options::default().with_read_timeout(Some(Duration::from_secs(1))))
+        .build()
+        .unwrap();
+
+    let mut response = client.get(&url).unwrap();
+
+    assert_eq!(response.body_mut().text().unwrap(), "helloworld");
+}
+
+#[test]
+fn read_timeout_does_not_apply_to_slow_handshakes() {
+    // Accept the connection, but never take part in the TLS handshake.
+    let url = common::serve_raw(|_| thread::sleep(Duration::from_secs(4))).replace("http://", "https://");
+
+    let client = chttp::Client::builder()
+        .options(chttp::Options::default()
+            .with_connect_timeout(Duration::from_secs(2))
+            .with_read_timeout(Some(Duration::from_secs(1))))
+        .build()
+        .unwrap();
+
+    assert!(match client.get(&url).as_ref().map_err(chttp::Error::kind) {
+        Err(&chttp::ErrorKind::ConnectTimeout) => true,
+        _ => false,
+    });
+}
+
+#[test]
+fn read_timeout_before_the_response_is_a_first_byte_timeout() {
+    let url = common::serve_raw(|_| thread::sleep(Duration::from_secs(4)));
+
+    let client = chttp::Client::builder()
+        .options(chttp::Options::default().with_read_timeout(Some(Duration::from_secs(1))))
+        .build()
+        .unwrap();
+
+    assert!(match client.get(&url).as_ref().map_err(chttp::Error::kind) {
+        Err(&chttp::ErrorKind::FirstByteTimeout) => true,
+        _ => false,
+    });
+}
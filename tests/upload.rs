@@ -0,0 +1,59 @@
+extern crate chttp;
+extern crate rouille;
+
+mod common;
+
+use chttp::{Body, Options};
+use std::env;
+use std::fs::File;
+use std::io::{Cursor, Write};
+
+/// Start a server that echoes how each request body was framed, and the body itself.
+fn echo_server() -> String {
+    common::echo_server(&["Content-Length", "Transfer-Encoding"])
+}
+
+#[test]
+fn sized_reader_is_sent_with_length() {
+    let url = echo_server();
+    let body = Body::from_reader_sized(Cursor::new(b"hello world".to_vec()), 11);
+
+    let mut response = chttp::Client::default().post(&url, body).unwrap();
+
+    assert_eq!(response.body_mut().text().unwrap(), "11\nnone\nhello world");
+}
+
+#[test]
+fn file_is_sent_with_length() {
+    let url = echo_server();
+    let path = env::temp_dir().join("chttp-upload-test.txt");
+    File::create(&path).unwrap().write_all(b"file contents").unwrap();
+
+    let mut response = chttp::Client::default().post(&url, File::open(&path).unwrap()).unwrap();
+
+    assert_eq!(response.body_mut().text().unwrap(), "13\nnone\nfile contents");
+}
+
+#[test]
+fn unsized_reader_is_sent_chunked() {
+    let url = echo_server();
+    let body = Body::from_reader(Cursor::new(b"hello world".to_vec()));
+
+    let mut response = chttp::Client::default().post(&url, body).unwrap();
+
+    assert_eq!(response.body_mut().text().unwrap(), "none\nchunked\nhello world");
+}
+
+#[test]
+fn unsized_reader_is_buffered_if_chunked_uploads_are_disabled() {
+    let url = echo_server();
+    let client = chttp::Client::builder()
+        .options(Options::default().with_chunked_uploads(false))
+        .build()
+        .unwrap();
+    let body = Body::from_reader(Cursor::new(b"hello world".to_vec()));
+
+    let mut response = client.post(&url, body).unwrap();
+
+    assert_eq!(response.body_mut().text().unwrap(), "11\nnone\nhello world");
+}